@@ -0,0 +1,96 @@
+// Agent configuration, from the standard OTEL_EXPORTER_OTLP_* variables (for the forwarding
+// endpoint), and OTEL_AGENT_* variables.
+use std::{collections::HashMap, env, time::Duration};
+
+pub const DEFAULT_SOCKET: &str = "/var/run/otel-agent.sock";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Grpc,
+    HttpProtobuf,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub socket: String,
+    pub socket_mode: u32,
+    pub protocol: Protocol,
+    pub endpoint: String,
+    pub headers: HashMap<String, String>,
+    pub timeout: Duration,
+    pub batch_delay: Duration,
+    pub max_batch_size: usize,
+    pub max_buffered_batches: usize,
+}
+
+impl Config {
+    pub fn from_env(socket: Option<String>) -> Self {
+        let protocol = match env::var("OTEL_EXPORTER_OTLP_PROTOCOL").as_deref() {
+            Ok("http/protobuf") => Protocol::HttpProtobuf,
+            Ok("grpc") | Err(_) => Protocol::Grpc,
+            Ok(other) => {
+                tracing::warn!("Unsupported protocol {}, using grpc", other);
+                Protocol::Grpc
+            }
+        };
+        let endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .ok()
+            .filter(|e| !e.is_empty())
+            .unwrap_or_else(|| match protocol {
+                Protocol::Grpc => "http://localhost:4317".to_string(),
+                Protocol::HttpProtobuf => "http://localhost:4318".to_string(),
+            });
+        Self {
+            socket: socket
+                .or_else(|| env::var("OTEL_AGENT_SOCKET").ok())
+                .unwrap_or_else(|| DEFAULT_SOCKET.to_string()),
+            socket_mode: env::var("OTEL_AGENT_SOCKET_MODE")
+                .ok()
+                .and_then(|mode| u32::from_str_radix(mode.trim(), 8).ok())
+                .unwrap_or(0o666),
+            protocol,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            headers: env::var("OTEL_EXPORTER_OTLP_HEADERS")
+                .map(|h| parse_headers(&h))
+                .unwrap_or_default(),
+            timeout: Duration::from_millis(get_u64("OTEL_EXPORTER_OTLP_TIMEOUT", 10000)),
+            batch_delay: Duration::from_millis(get_u64("OTEL_AGENT_BATCH_DELAY", 1000)),
+            max_batch_size: get_u64("OTEL_AGENT_MAX_BATCH_SIZE", 2048).max(1) as usize,
+            max_buffered_batches: get_u64("OTEL_AGENT_MAX_BUFFERED_BATCHES", 100).max(1) as usize,
+        }
+    }
+}
+
+fn get_u64(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+/// Parse a list of headers (key1=value1,key2=value2).
+fn parse_headers(s: &str) -> HashMap<String, String> {
+    s.split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() {
+                return None;
+            }
+            Some((key.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers("api-key=secret, x-tenant=a=b,invalid,=empty");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["api-key"], "secret");
+        assert_eq!(headers["x-tenant"], "a=b");
+    }
+}
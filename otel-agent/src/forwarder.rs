@@ -0,0 +1,297 @@
+// Batches requests received from all workers, and forwards them to the OTLP endpoint. Batches which
+// fail with a retryable error are buffered (up to max_buffered_batches, oldest dropped first) and
+// retried with exponential backoff.
+use crate::config::{Config, Protocol};
+use opentelemetry_proto::tonic::{
+    collector::{
+        logs::v1::{logs_service_client::LogsServiceClient, ExportLogsServiceRequest},
+        trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    },
+    logs::v1::ResourceLogs,
+    trace::v1::ResourceSpans,
+};
+use prost::Message;
+use std::{
+    collections::VecDeque,
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc::Receiver, oneshot},
+    time::{self, Instant},
+};
+use tonic::{
+    metadata::{MetadataKey, MetadataMap, MetadataValue},
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub enum Batch {
+    Traces(ExportTraceServiceRequest),
+    Logs(ExportLogsServiceRequest),
+}
+
+impl Batch {
+    fn kind(&self) -> &'static str {
+        match self {
+            Batch::Traces(_) => "traces",
+            Batch::Logs(_) => "logs",
+        }
+    }
+}
+
+enum ExportError {
+    Retryable(String),
+    Permanent(String),
+}
+
+enum Client {
+    Grpc {
+        traces: Box<TraceServiceClient<Channel>>,
+        logs: Box<LogsServiceClient<Channel>>,
+        metadata: MetadataMap,
+    },
+    Http {
+        client: reqwest::Client,
+        traces_url: String,
+        logs_url: String,
+        headers: Vec<(String, String)>,
+    },
+}
+
+impl Client {
+    fn new(config: &Config) -> Result<Self, String> {
+        match config.protocol {
+            Protocol::Grpc => {
+                let channel = Endpoint::from_shared(config.endpoint.clone())
+                    .map_err(|err| format!("invalid endpoint {}: {}", config.endpoint, err))?
+                    .timeout(config.timeout)
+                    .connect_lazy();
+                let mut metadata = MetadataMap::new();
+                for (key, value) in &config.headers {
+                    match (key.to_lowercase().parse::<MetadataKey<_>>(), value.parse::<MetadataValue<_>>()) {
+                        (Ok(key), Ok(value)) => {
+                            metadata.insert(key, value);
+                        }
+                        _ => tracing::warn!("Ignoring invalid OTLP header {}", key),
+                    }
+                }
+                Ok(Client::Grpc {
+                    traces: Box::new(TraceServiceClient::new(channel.clone())),
+                    logs: Box::new(LogsServiceClient::new(channel)),
+                    metadata,
+                })
+            }
+            Protocol::HttpProtobuf => {
+                let client = reqwest::Client::builder()
+                    .timeout(config.timeout)
+                    .build()
+                    .map_err(|err| format!("failed to create http client: {}", err))?;
+                Ok(Client::Http {
+                    client,
+                    traces_url: format!("{}/v1/traces", config.endpoint),
+                    logs_url: format!("{}/v1/logs", config.endpoint),
+                    headers: config.headers.clone().into_iter().collect(),
+                })
+            }
+        }
+    }
+
+    async fn export(&mut self, batch: &Batch) -> Result<(), ExportError> {
+        match self {
+            Client::Grpc { traces, logs, metadata } => {
+                let result = match batch {
+                    Batch::Traces(request) => {
+                        let mut request = tonic::Request::new(request.clone());
+                        *request.metadata_mut() = metadata.clone();
+                        traces.export(request).await.map(|_| ())
+                    }
+                    Batch::Logs(request) => {
+                        let mut request = tonic::Request::new(request.clone());
+                        *request.metadata_mut() = metadata.clone();
+                        logs.export(request).await.map(|_| ())
+                    }
+                };
+                result.map_err(|status| match status.code() {
+                    Code::Cancelled | Code::DeadlineExceeded | Code::ResourceExhausted | Code::Aborted
+                    | Code::OutOfRange | Code::Unavailable | Code::DataLoss => ExportError::Retryable(status.to_string()),
+                    _ => ExportError::Permanent(status.to_string()),
+                })
+            }
+            Client::Http { client, traces_url, logs_url, headers } => {
+                let (url, body) = match batch {
+                    Batch::Traces(request) => (traces_url.as_str(), request.encode_to_vec()),
+                    Batch::Logs(request) => (logs_url.as_str(), request.encode_to_vec()),
+                };
+                let mut request = client
+                    .post(url)
+                    .header("content-type", "application/x-protobuf")
+                    .body(body);
+                for (key, value) in headers.iter() {
+                    request = request.header(key.as_str(), value.as_str());
+                }
+                match request.send().await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => match response.status().as_u16() {
+                        429 | 502 | 503 | 504 => Err(ExportError::Retryable(format!("http status {}", response.status()))),
+                        _ => Err(ExportError::Permanent(format!("http status {}", response.status()))),
+                    },
+                    Err(err) => Err(ExportError::Retryable(err.to_string())),
+                }
+            }
+        }
+    }
+}
+
+/// Spans and logs received since the last export.
+#[derive(Default)]
+struct Pending {
+    resource_spans: Vec<ResourceSpans>,
+    resource_logs: Vec<ResourceLogs>,
+    items: usize,
+}
+
+impl Pending {
+    fn add(&mut self, signal: u8, payload: &[u8]) {
+        match signal {
+            crate::frame::FRAME_TRACES => match ExportTraceServiceRequest::decode(payload) {
+                Ok(request) => {
+                    self.items += request.resource_spans
+                        .iter()
+                        .flat_map(|rs| rs.scope_spans.iter())
+                        .map(|ss| ss.spans.len())
+                        .sum::<usize>();
+                    self.resource_spans.extend(request.resource_spans);
+                }
+                Err(err) => tracing::warn!("Ignoring invalid traces frame: {}", err),
+            },
+            crate::frame::FRAME_LOGS => match ExportLogsServiceRequest::decode(payload) {
+                Ok(request) => {
+                    self.items += request.resource_logs
+                        .iter()
+                        .flat_map(|rl| rl.scope_logs.iter())
+                        .map(|sl| sl.log_records.len())
+                        .sum::<usize>();
+                    self.resource_logs.extend(request.resource_logs);
+                }
+                Err(err) => tracing::warn!("Ignoring invalid logs frame: {}", err),
+            },
+            other => tracing::warn!("Ignoring frame with unknown signal {}", other),
+        }
+    }
+
+    fn take(&mut self) -> Vec<Batch> {
+        let mut batches = Vec::new();
+        if !self.resource_spans.is_empty() {
+            batches.push(Batch::Traces(ExportTraceServiceRequest {
+                resource_spans: std::mem::take(&mut self.resource_spans),
+            }));
+        }
+        if !self.resource_logs.is_empty() {
+            batches.push(Batch::Logs(ExportLogsServiceRequest {
+                resource_logs: std::mem::take(&mut self.resource_logs),
+            }));
+        }
+        self.items = 0;
+        batches
+    }
+}
+
+pub struct Forwarder {
+    client: Client,
+    pending: Pending,
+    outbox: VecDeque<Batch>,
+    batch_delay: Duration,
+    max_batch_size: usize,
+    max_buffered_batches: usize,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl Forwarder {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        Ok(Self {
+            client: Client::new(config)?,
+            pending: Pending::default(),
+            outbox: VecDeque::new(),
+            batch_delay: config.batch_delay,
+            max_batch_size: config.max_batch_size,
+            max_buffered_batches: config.max_buffered_batches,
+            backoff: MIN_BACKOFF,
+            retry_at: None,
+        })
+    }
+
+    /// Receive frames until shutdown, then make a final attempt to export anything buffered.
+    pub async fn run(mut self, mut receiver: Receiver<(u8, Vec<u8>)>, mut shutdown: oneshot::Receiver<()>) {
+        let mut ticker = time::interval(self.batch_delay);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                frame = receiver.recv() => match frame {
+                    Some((signal, payload)) => {
+                        self.pending.add(signal, &payload);
+                        if self.pending.items >= self.max_batch_size {
+                            self.enqueue_pending();
+                            self.send_outbox().await;
+                        }
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    self.enqueue_pending();
+                    self.send_outbox().await;
+                }
+            }
+        }
+        while let Ok((signal, payload)) = receiver.try_recv() {
+            self.pending.add(signal, &payload);
+        }
+        self.enqueue_pending();
+        tracing::info!("Shutting down, exporting {} buffered batches", self.outbox.len());
+        self.retry_at = None;
+        self.send_outbox().await;
+        if !self.outbox.is_empty() {
+            tracing::warn!("{} batches could not be exported before shutdown", self.outbox.len());
+        }
+    }
+
+    fn enqueue_pending(&mut self) {
+        for batch in self.pending.take() {
+            if self.outbox.len() >= self.max_buffered_batches
+                && let Some(dropped) = self.outbox.pop_front()
+            {
+                tracing::warn!("Export buffer is full, dropping oldest {} batch", dropped.kind());
+            }
+            self.outbox.push_back(batch);
+        }
+    }
+
+    async fn send_outbox(&mut self) {
+        if self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return;
+        }
+        while let Some(batch) = self.outbox.front() {
+            match self.client.export(batch).await {
+                Ok(_) => {
+                    tracing::debug!("Exported {} batch", batch.kind());
+                    self.outbox.pop_front();
+                    self.backoff = MIN_BACKOFF;
+                    self.retry_at = None;
+                }
+                Err(ExportError::Permanent(err)) => {
+                    tracing::warn!("Dropping {} batch after permanent export failure: {}", batch.kind(), err);
+                    self.outbox.pop_front();
+                }
+                Err(ExportError::Retryable(err)) => {
+                    tracing::warn!("Export of {} batch failed, retrying in {:?}: {}", batch.kind(), self.backoff, err);
+                    self.retry_at = Some(Instant::now() + self.backoff);
+                    self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                    return;
+                }
+            }
+        }
+    }
+}
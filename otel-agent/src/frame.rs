@@ -0,0 +1,46 @@
+// Frames written by the extension (see otel/src/agent.rs):
+//   [signal: u8][length: u32, big-endian][protobuf-encoded Export*ServiceRequest]
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub const FRAME_TRACES: u8 = 1;
+pub const FRAME_LOGS: u8 = 2;
+
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Read one frame, or None if the connection was closed between frames.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let signal = match reader.read_u8().await {
+        Ok(signal) => signal,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let length = reader.read_u32().await? as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes is too large", length)));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some((signal, payload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reads_frames_until_eof() {
+        let data: &[u8] = &[1, 0, 0, 0, 2, b'h', b'i', 2, 0, 0, 0, 0];
+        let mut reader = data;
+        assert_eq!(read_frame(&mut reader).await.unwrap(), Some((FRAME_TRACES, b"hi".to_vec())));
+        assert_eq!(read_frame(&mut reader).await.unwrap(), Some((FRAME_LOGS, Vec::new())));
+        assert_eq!(read_frame(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frame_is_an_error() {
+        let data: &[u8] = &[1, 0, 0, 0, 5, b'h'];
+        let mut reader = data;
+        assert!(read_frame(&mut reader).await.is_err());
+    }
+}
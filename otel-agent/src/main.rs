@@ -0,0 +1,123 @@
+// otel-agent: receives OTLP export requests from PHP workers (with otel.agent.socket set) over a
+// Unix domain socket, batches them across workers, and forwards them to an OTLP endpoint.
+mod config;
+mod forwarder;
+mod frame;
+
+use config::Config;
+use forwarder::Forwarder;
+use std::{
+    env,
+    fs,
+    os::unix::fs::PermissionsExt,
+    process,
+};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    signal::unix::{signal, SignalKind},
+    sync::{
+        mpsc::{self, Sender},
+        oneshot,
+    },
+};
+use tracing_subscriber::EnvFilter;
+
+const CHANNEL_SIZE: usize = 10_000;
+
+const USAGE: &str = "Usage: otel-agent [--socket <path>]
+
+Receives OTLP batches from the otel extension over a Unix socket, and forwards them to
+OTEL_EXPORTER_OTLP_ENDPOINT (OTEL_EXPORTER_OTLP_PROTOCOL, _HEADERS and _TIMEOUT are also
+supported). See the README for OTEL_AGENT_* settings. Log level is set via RUST_LOG.";
+
+fn parse_args() -> Option<String> {
+    let mut args = env::args().skip(1);
+    let mut socket = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" => socket = args.next(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            other => {
+                eprintln!("Unknown argument {}\n\n{}", other, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    socket
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let config = Config::from_env(parse_args());
+    let forwarder = match Forwarder::new(&config) {
+        Ok(forwarder) => forwarder,
+        Err(err) => {
+            tracing::error!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    // remove a stale socket left by a previous run
+    let _ = fs::remove_file(&config.socket);
+    let listener = match UnixListener::bind(&config.socket) {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!("Failed to bind {}: {}", config.socket, err);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = fs::set_permissions(&config.socket, fs::Permissions::from_mode(config.socket_mode)) {
+        tracing::warn!("Failed to set permissions on {}: {}", config.socket, err);
+    }
+    tracing::info!("Listening on {}, forwarding to {} ({:?})", config.socket, config.endpoint, config.protocol);
+
+    let (sender, receiver) = mpsc::channel(CHANNEL_SIZE);
+    let (shutdown, shutdown_receiver) = oneshot::channel();
+    let forwarder = tokio::spawn(forwarder.run(receiver, shutdown_receiver));
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream, sender.clone()));
+                }
+                Err(err) => tracing::warn!("Failed to accept connection: {}", err),
+            },
+            _ = sigterm.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    tracing::info!("Shutting down");
+    drop(listener);
+    let _ = fs::remove_file(&config.socket);
+    let _ = shutdown.send(());
+    // bound the final export, so that an unreachable endpoint cannot block shutdown
+    let _ = tokio::time::timeout(config.timeout * 2, forwarder).await;
+}
+
+/// Read frames from one worker connection until it is closed.
+async fn handle_connection(mut stream: UnixStream, sender: Sender<(u8, Vec<u8>)>) {
+    loop {
+        match frame::read_frame(&mut stream).await {
+            Ok(Some(frame)) => {
+                if sender.send(frame).await.is_err() {
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(err) => {
+                tracing::warn!("Closing connection after invalid frame: {}", err);
+                return;
+            }
+        }
+    }
+}
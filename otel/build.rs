@@ -17,6 +17,14 @@ fn main() {
     } else {
         println!("cargo::rustc-cfg=otel_observer_not_supported");
     }
+    println!("cargo::rustc-check-cfg=cfg(otel_fiber_supported)");
+    if PHP_MAJOR_VERSION > 8 || (PHP_MAJOR_VERSION == 8 && PHP_MINOR_VERSION >= 1) {
+        println!("cargo::rustc-cfg=otel_fiber_supported");
+    }
+    println!("cargo::rustc-check-cfg=cfg(otel_zts)");
+    if USING_ZTS != 0 {
+        println!("cargo::rustc-cfg=otel_zts");
+    }
 
     //get metadata about interesting dependencies
     let metadata = MetadataCommand::new()
@@ -0,0 +1,198 @@
+// Local agent mode. Instead of each worker connecting to the OTLP endpoint, encoded OTLP export
+// requests are written to a Unix domain socket, where the otel-agent daemon (see /otel-agent)
+// batches them across workers and forwards them. Each request is sent as one frame:
+//   [signal: u8][length: u32, big-endian][protobuf-encoded Export*ServiceRequest]
+use crate::{
+    config::{
+        self,
+        otlp::{self, Signal},
+    },
+    otlp_encode::{encode_logs, spans_request},
+};
+use opentelemetry_proto::{
+    tonic::collector::trace::v1::ExportTraceServiceRequest,
+    transform::common::tonic::ResourceAttributesWithSchema,
+};
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    logs::{LogBatch, LogExporter},
+    trace::{SpanData, SpanExporter},
+    Resource,
+};
+use phper::ini::ini_get;
+use prost::Message;
+use std::{
+    ffi::CStr,
+    io::Write,
+    os::unix::net::UnixStream,
+    sync::Mutex,
+    time::Duration,
+};
+
+pub const FRAME_TRACES: u8 = 1;
+pub const FRAME_LOGS: u8 = 2;
+
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The agent socket path (otel.agent.socket), if agent mode is enabled.
+pub fn socket_path() -> Option<String> {
+    ini_get::<Option<&CStr>>(config::ini::OTEL_AGENT_SOCKET)
+        .and_then(|path| path.to_str().ok())
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+}
+
+/// The agent exports with its own OTLP configuration, so an endpoint or headers configured for the
+/// app (eg in .env) are not used. Say so, rather than ignoring them silently.
+fn warn_ignored_otlp_config(signal: Signal) {
+    let otlp_config = otlp::get_otlp_config(signal, false);
+    if otlp_config.endpoint.is_some() || !otlp_config.headers.is_empty() {
+        tracing::warn!(
+            "OTLP endpoint and headers are ignored for {} in agent mode, the agent's configuration is used",
+            signal.name()
+        );
+    }
+}
+
+/// Encode a payload as a frame.
+pub fn encode_frame(signal: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.push(signal);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// A connection to the agent, opened on first use and re-opened once if a write fails (eg the agent
+/// has restarted).
+#[derive(Debug)]
+struct AgentConnection {
+    path: String,
+    stream: Mutex<Option<UnixStream>>,
+}
+
+impl AgentConnection {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            stream: Mutex::new(None),
+        }
+    }
+
+    fn connect(&self) -> std::io::Result<UnixStream> {
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    fn send(&self, signal: u8, payload: &[u8]) -> OTelSdkResult {
+        let frame = encode_frame(signal, payload);
+        let mut stream = match self.stream.lock() {
+            Ok(stream) => stream,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for attempt in 0..2 {
+            if stream.is_none() {
+                match self.connect() {
+                    Ok(connected) => *stream = Some(connected),
+                    Err(err) => {
+                        return Err(OTelSdkError::InternalFailure(format!("failed to connect to agent socket {}: {}", self.path, err)));
+                    }
+                }
+            }
+            match stream.as_mut().map(|s| s.write_all(&frame)) {
+                Some(Ok(_)) => return Ok(()),
+                Some(Err(err)) => {
+                    tracing::debug!("write to agent socket failed (attempt {}): {}", attempt + 1, err);
+                    *stream = None;
+                }
+                None => {}
+            }
+        }
+        Err(OTelSdkError::InternalFailure(format!("failed to write to agent socket {}", self.path)))
+    }
+}
+
+#[derive(Debug)]
+pub struct AgentSpanExporter {
+    connection: AgentConnection,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl AgentSpanExporter {
+    pub fn new(path: String) -> Self {
+        warn_ignored_otlp_config(Signal::Traces);
+        Self {
+            connection: AgentConnection::new(path),
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+
+    /// Send a request which is already built (eg with the spans of several resources).
+    pub fn export_request(&self, request: &ExportTraceServiceRequest) -> OTelSdkResult {
+        self.connection.send(FRAME_TRACES, &request.encode_to_vec())
+    }
+}
+
+impl SpanExporter for AgentSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        self.export_request(&spans_request(batch, &self.resource))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+    }
+}
+
+#[derive(Debug)]
+pub struct AgentLogExporter {
+    connection: AgentConnection,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl AgentLogExporter {
+    pub fn new(path: String) -> Self {
+        warn_ignored_otlp_config(Signal::Logs);
+        Self {
+            connection: AgentConnection::new(path),
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+}
+
+impl LogExporter for AgentLogExporter {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        self.connection.send(FRAME_LOGS, &encode_logs(batch, &self.resource))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, os::unix::net::UnixListener};
+
+    #[test]
+    fn test_encode_frame() {
+        assert_eq!(encode_frame(FRAME_LOGS, b"abc"), vec![2, 0, 0, 0, 3, b'a', b'b', b'c']);
+    }
+
+    #[test]
+    fn test_connects_once_agent_is_listening() {
+        let path = std::env::temp_dir().join(format!("otel-agent-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let connection = AgentConnection::new(path.to_string_lossy().to_string());
+        assert!(connection.send(FRAME_TRACES, b"x").is_err());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        connection.send(FRAME_TRACES, b"one").unwrap();
+        let (mut accepted, _) = listener.accept().unwrap();
+        let mut received = [0u8; 8];
+        accepted.read_exact(&mut received).unwrap();
+        assert_eq!(&received, &[1, 0, 0, 0, 3, b'o', b'n', b'e']);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+// Panic isolation for plugin hooks. A panic inside a pre/post hook must not unwind into the Zend
+// engine, so every hook is run inside catch_unwind. Failures are counted per plugin, and once a
+// plugin reaches the configured threshold it is disabled for the remaining life of the worker.
+use crate::config;
+use phper::ini::ini_get;
+use once_cell::sync::Lazy;
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::RwLock,
+};
+
+#[derive(Default)]
+struct PluginHealth {
+    failures: u32,
+    disabled: bool,
+}
+
+static PLUGIN_HEALTH: Lazy<RwLock<HashMap<String, PluginHealth>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Run a plugin hook, catching and logging any panic. Hooks for a plugin which has been disabled
+/// are skipped.
+pub fn run_hook<F: FnOnce()>(plugin_name: &str, hook: F) {
+    if is_disabled(plugin_name) {
+        tracing::trace!("plugin {} is disabled, skipping hook", plugin_name);
+        return;
+    }
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(hook)) {
+        record_failure(plugin_name, panic_message(payload.as_ref()));
+    }
+}
+
+/// Whether a plugin has been disabled by the circuit breaker.
+pub fn is_disabled(plugin_name: &str) -> bool {
+    PLUGIN_HEALTH
+        .read()
+        .map(|health| health.get(plugin_name).is_some_and(|h| h.disabled))
+        .unwrap_or(false)
+}
+
+/// Get (failure count, disabled) for a plugin.
+pub fn get_plugin_health(plugin_name: &str) -> (u32, bool) {
+    PLUGIN_HEALTH
+        .read()
+        .ok()
+        .and_then(|health| health.get(plugin_name).map(|h| (h.failures, h.disabled)))
+        .unwrap_or((0, false))
+}
+
+fn record_failure(plugin_name: &str, message: String) {
+    let threshold = get_failure_threshold();
+    let mut health = match PLUGIN_HEALTH.write() {
+        Ok(health) => health,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let entry = health.entry(plugin_name.to_string()).or_default();
+    entry.failures += 1;
+    tracing::error!("plugin {} hook panicked ({} failures): {}", plugin_name, entry.failures, message);
+    if threshold > 0 && entry.failures >= threshold && !entry.disabled {
+        entry.disabled = true;
+        tracing::error!("plugin {} disabled after {} failures", plugin_name, entry.failures);
+    }
+}
+
+fn get_failure_threshold() -> u32 {
+    let threshold = ini_get::<i64>(config::ini::OTEL_AUTO_PLUGIN_FAILURE_THRESHOLD);
+    threshold.clamp(0, u32::MAX as i64) as u32
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -2,6 +2,7 @@ use phper::{
     eg,
     objects::ZObj,
     strings::ZStr,
+    sys,
     values::ExecuteData,
 };
 use opentelemetry::{
@@ -80,6 +81,29 @@ fn get_file_and_line(execute_data: &ExecuteData) -> Option<(String, u32)> {
     }
 }
 
+/// Is the function being executed a generator?
+pub fn is_generator(execute_data: &ExecuteData) -> bool {
+    unsafe {
+        let func = execute_data.func().as_ptr();
+        (*func).type_ as u32 == sys::ZEND_USER_FUNCTION
+            && (*func).op_array.fn_flags & sys::ZEND_ACC_GENERATOR != 0
+    }
+}
+
+/// In an observer end handler for a generator, is the generator suspending (yield) rather than
+/// finishing? The engine passes &generator->value when yielding, and &generator->retval (or null,
+/// for an exception) when the generator completes.
+#[cfg(otel_observer_supported)]
+pub fn is_generator_suspending(execute_data: &ExecuteData, retval: *mut sys::zval) -> bool {
+    if retval.is_null() {
+        return false;
+    }
+    unsafe {
+        let generator = (*execute_data.as_ptr()).return_value as *mut sys::zend_generator;
+        !generator.is_null() && std::ptr::eq(retval, &raw mut (*generator).value)
+    }
+}
+
 pub fn get_global_exception() -> Option<&'static mut ZObj> {
     unsafe { ZObj::try_from_mut_ptr(eg!(exception)) }
 }
@@ -2,6 +2,7 @@
 pub mod observer;
 #[cfg(otel_observer_not_supported)]
 pub mod execute;
+pub mod circuit_breaker;
 pub mod execute_data;
 pub mod plugin_manager;
 pub mod utils;
@@ -11,6 +11,8 @@ use crate::{
         execute_data::{
             get_fqn,
             get_global_exception,
+            is_generator,
+            is_generator_suspending,
         },
         plugin::{
             FunctionObserver,
@@ -19,6 +21,7 @@ use crate::{
             get_global as get_plugin_manager,
         },
     },
+    context::storage,
 };
 use std::{
     collections::HashMap,
@@ -34,6 +37,81 @@ pub fn init() {
         sys::zend_observer_fcall_register(Some(observer_instrument));
     }
     tracing::debug!("registered fcall handlers");
+    #[cfg(otel_fiber_supported)]
+    {
+        unsafe {
+            sys::zend_observer_fiber_switch_register(Some(observe_fiber_switch));
+        }
+        tracing::debug!("registered fiber switch handler");
+    }
+    unsafe { hook_generator_destroy() };
+}
+
+type CreateObject = unsafe extern "C" fn(class_type: *mut sys::zend_class_entry) -> *mut sys::zend_object;
+type DestroyObject = unsafe extern "C" fn(object: *mut sys::zend_object);
+
+static UPSTREAM_GENERATOR_CREATE: OnceLock<CreateObject> = OnceLock::new();
+static UPSTREAM_GENERATOR_DTOR: OnceLock<DestroyObject> = OnceLock::new();
+static GENERATOR_HANDLERS: OnceLock<sys::zend_object_handlers> = OnceLock::new();
+
+/// A generator destroyed before it completes never reaches the observer's end handler, so generator
+/// objects get a copy of the engine's handlers with a destructor which discards their suspended
+/// context.
+unsafe fn hook_generator_destroy() {
+    unsafe {
+        let class_entry = sys::zend_ce_generator;
+        if class_entry.is_null() {
+            return;
+        }
+        if let Some(upstream) = (*class_entry).create_object {
+            UPSTREAM_GENERATOR_CREATE.get_or_init(|| upstream);
+            (*class_entry).create_object = Some(create_generator);
+            tracing::debug!("hooked generator destruction");
+        }
+    }
+}
+
+unsafe extern "C" fn create_generator(class_type: *mut sys::zend_class_entry) -> *mut sys::zend_object {
+    let Some(upstream) = UPSTREAM_GENERATOR_CREATE.get() else {
+        return std::ptr::null_mut();
+    };
+    unsafe {
+        let object = upstream(class_type);
+        if !object.is_null() {
+            let handlers = GENERATOR_HANDLERS.get_or_init(|| {
+                let mut handlers = *(*object).handlers;
+                if let Some(dtor) = handlers.dtor_obj {
+                    UPSTREAM_GENERATOR_DTOR.get_or_init(|| dtor);
+                }
+                handlers.dtor_obj = Some(destroy_generator);
+                handlers
+            });
+            (*object).handlers = handlers;
+        }
+        object
+    }
+}
+
+unsafe extern "C" fn destroy_generator(object: *mut sys::zend_object) {
+    unsafe {
+        // the engine's destructor frees execute_data (after running any finally blocks, which
+        // resume the generator), so the key is read first, and discarded after
+        let execute_data = (*(object as *mut sys::zend_generator)).execute_data;
+        if let Some(upstream) = UPSTREAM_GENERATOR_DTOR.get() {
+            upstream(object);
+        }
+        if !execute_data.is_null() {
+            storage::discard_generator(execute_data as *mut ExecuteData);
+        }
+    }
+}
+
+/// Get the observer for a function, without holding the lock while hooks run (hooks may call
+/// into PHP, which can re-enter observer_instrument).
+fn get_function_observer(fqn: &str) -> Option<Arc<FunctionObserver>> {
+    let observers = FUNCTION_OBSERVERS.get().expect("Function observer not initialized");
+    let lock = observers.read().unwrap();
+    lock.get(fqn).cloned()
 }
 
 pub unsafe extern "C" fn observer_instrument(execute_data: *mut sys::zend_execute_data) -> sys::zend_observer_fcall_handlers {
@@ -69,9 +147,12 @@ pub unsafe extern "C" fn observer_instrument(execute_data: *mut sys::zend_execut
 pub unsafe extern "C" fn pre_observe_c_function(execute_data: *mut sys::zend_execute_data) {
     if let Some(exec_data) = unsafe{ExecuteData::try_from_mut_ptr(execute_data)} {
         if let Some(fqn) = get_fqn(exec_data) {
-            let observers = FUNCTION_OBSERVERS.get().expect("Function observer not initialized");
-            let lock = observers.read().unwrap();
-            if let Some(observer) = lock.get(&fqn) {
+            if let Some(observer) = get_function_observer(&fqn) {
+                // a generator is observed each time it is resumed, but hooks only run for the first
+                if is_generator(exec_data) && storage::resume_guard(execute_data as *mut ExecuteData) {
+                    tracing::trace!("resuming generator: {}", fqn);
+                    return;
+                }
                 if observer.has_hooks() {
                     for hook in observer.pre_hooks() {
                         tracing::trace!("running pre hook: {}", fqn);
@@ -87,9 +168,13 @@ pub unsafe extern "C" fn pre_observe_c_function(execute_data: *mut sys::zend_exe
 pub unsafe extern "C" fn post_observe_c_function(execute_data: *mut sys::zend_execute_data, retval: *mut sys::zval) {
     if let Some(exec_data) = unsafe{ExecuteData::try_from_mut_ptr(execute_data)} {
         if let Some(fqn) = get_fqn(exec_data) {
-            let observers = FUNCTION_OBSERVERS.get().expect("Function observer not initialized");
-            let lock = observers.read().unwrap();
-            if let Some(observer) = lock.get(&fqn) {
+            if let Some(observer) = get_function_observer(&fqn) {
+                // a yielding generator keeps its span open until it completes
+                if is_generator(exec_data) && is_generator_suspending(exec_data, retval) {
+                    tracing::trace!("suspending generator: {}", fqn);
+                    storage::suspend_guard(execute_data as *mut ExecuteData);
+                    return;
+                }
                 let retval = if retval.is_null() {
                     &mut ZVal::from(())
                 } else {
@@ -102,4 +187,10 @@ pub unsafe extern "C" fn post_observe_c_function(execute_data: *mut sys::zend_ex
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(otel_fiber_supported)]
+unsafe extern "C" fn observe_fiber_switch(from: *mut sys::zend_fiber_context, to: *mut sys::zend_fiber_context) {
+    let from_finished = unsafe { (*from).status == sys::zend_fiber_status_ZEND_FIBER_STATUS_DEAD };
+    storage::switch_fiber(from as usize, to as usize, from_finished);
+}
@@ -5,6 +5,8 @@ use phper::{
 };
 
 // Submodules
+pub mod exec;
+pub mod frankenphp;
 pub mod laminas;
 pub mod psr18;
 #[cfg(feature = "test")]
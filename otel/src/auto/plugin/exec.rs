@@ -0,0 +1,318 @@
+use crate::{
+    config,
+    auto::{
+        plugin::{Handler, HandlerList, HandlerSlice, HandlerCallbacks, Plugin},
+        utils::{start_and_activate_span, record_exception},
+    },
+    context::storage::{take_guard},
+    trace::tracer_provider,
+};
+use opentelemetry::{
+    Array,
+    KeyValue,
+    Context,
+    StringValue,
+    Value,
+    global,
+    trace::{
+        SpanKind,
+        Status,
+        TraceContextExt,
+        TracerProvider,
+    },
+};
+use opentelemetry_semantic_conventions as SemConv;
+use std::{
+    sync::Arc,
+    collections::HashMap,
+    path::Path,
+};
+use phper::{
+    arrays::ZArray,
+    objects::ZObj,
+    values::{
+        ExecuteData,
+        ZVal,
+    },
+};
+
+// Process execution plugin. exec, shell_exec, system, passthru and proc_open calls get a span with
+// process.command, and the exit code when the result code argument is passed (exec, system,
+// passthru). Requires PHP 8.2+, since these are internal functions.
+// Opt-in, since command lines often contain credentials, and propagation modifies the call:
+// - OTEL_PHP_EXEC_CAPTURE_COMMAND_LINE=true records process.command_line (or process.command_args)
+// - OTEL_PHP_EXEC_PROPAGATE_CONTEXT=true passes the trace context to the child process as
+//   TRACEPARENT, TRACESTATE and BAGGAGE environment variables (which a PHP CLI child picks up for
+//   its root span). For proc_open with an env argument, they are added to it; otherwise shell
+//   commands are prefixed with an export, on POSIX shells only.
+
+/// Carrier keys, and the environment variables they are passed to the child process as.
+const ENV_VARS: &[(&str, &str)] = &[
+    ("traceparent", "TRACEPARENT"),
+    ("tracestate", "TRACESTATE"),
+    ("baggage", "BAGGAGE"),
+];
+
+#[derive(Clone, Copy)]
+enum Function {
+    Exec,
+    ShellExec,
+    System,
+    Passthru,
+    ProcOpen,
+}
+
+impl Function {
+    fn name(&self) -> &'static str {
+        match self {
+            Function::Exec => "exec",
+            Function::ShellExec => "shell_exec",
+            Function::System => "system",
+            Function::Passthru => "passthru",
+            Function::ProcOpen => "proc_open",
+        }
+    }
+
+    /// Position of the by-reference result code argument, if the function has one.
+    fn result_code_arg(&self) -> Option<usize> {
+        match self {
+            Function::Exec => Some(2),
+            Function::System | Function::Passthru => Some(1),
+            Function::ShellExec | Function::ProcOpen => None,
+        }
+    }
+}
+
+pub struct ExecPlugin {
+    handlers: HandlerList,
+}
+
+impl ExecPlugin {
+    pub fn new() -> Self {
+        Self {
+            handlers: vec![
+                Arc::new(ExecHandler { function: Function::Exec }),
+                Arc::new(ExecHandler { function: Function::ShellExec }),
+                Arc::new(ExecHandler { function: Function::System }),
+                Arc::new(ExecHandler { function: Function::Passthru }),
+                Arc::new(ExecHandler { function: Function::ProcOpen }),
+            ],
+        }
+    }
+}
+
+impl Plugin for ExecPlugin {
+    fn get_handlers(&self) -> &HandlerSlice {
+        &self.handlers
+    }
+    fn get_name(&self) -> &str {
+        "exec"
+    }
+}
+
+pub struct ExecHandler {
+    function: Function,
+}
+
+impl Handler for ExecHandler {
+    fn get_targets(&self) -> Vec<(Option<&'static str>, &'static str)> {
+        vec![
+            (None, self.function.name()),
+        ]
+    }
+    fn get_callbacks(&self) -> HandlerCallbacks {
+        let function = self.function;
+        HandlerCallbacks {
+            pre_observe: Some(Box::new(move |exec_data| unsafe {
+                Self::pre_callback(exec_data, function)
+            })),
+            post_observe: Some(Box::new(move |exec_data, retval, exception| unsafe {
+                Self::post_callback(exec_data, retval, exception, function)
+            })),
+        }
+    }
+}
+
+impl ExecHandler {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData, function: Function) {
+        let exec_data_ref = unsafe {&mut *exec_data};
+        if exec_data_ref.num_args() < 1 {
+            return;
+        }
+        let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.exec");
+        let mut attributes = vec![];
+        let command_zval: &mut ZVal = exec_data_ref.get_mut_parameter(0);
+        let command_line = command_zval.as_z_str().and_then(|s| s.to_str().ok()).map(|s| s.to_owned());
+        let command_args: Vec<String> = command_zval
+            .as_z_arr()
+            .map(|args| {
+                args.iter()
+                    .filter_map(|(_, arg)| arg.as_z_str()?.to_str().ok().map(|s| s.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let command = match &command_line {
+            Some(command_line) => command_name(command_line),
+            None => command_args.first().map(|arg| base_name(arg)),
+        };
+        if let Some(command) = &command {
+            attributes.push(KeyValue::new(SemConv::attribute::PROCESS_COMMAND, command.clone()));
+        }
+        if is_enabled("OTEL_PHP_EXEC_CAPTURE_COMMAND_LINE") {
+            if let Some(command_line) = &command_line {
+                attributes.push(KeyValue::new(SemConv::attribute::PROCESS_COMMAND_LINE, command_line.clone()));
+            } else if !command_args.is_empty() {
+                let args = command_args.iter().cloned().map(StringValue::from).collect();
+                attributes.push(KeyValue::new(SemConv::attribute::PROCESS_COMMAND_ARGS, Value::Array(Array::String(args))));
+            }
+        }
+        let name = match &command {
+            Some(command) => format!("{} {}", function.name(), command),
+            None => function.name().to_string(),
+        };
+
+        start_and_activate_span(tracer, &name, attributes, exec_data, SpanKind::Internal);
+        if !is_enabled("OTEL_PHP_EXEC_PROPAGATE_CONTEXT") {
+            return;
+        }
+
+        //now inject the trace context into the child's environment, using the span we just started
+        let mut carrier = HashMap::new();
+        global::get_text_map_propagator(|prop| prop.inject_context(&Context::current(), &mut carrier));
+        let vars = env_vars(&carrier);
+        if vars.is_empty() {
+            return;
+        }
+        let exec_data_ref = unsafe {&mut *exec_data};
+        if let Function::ProcOpen = function {
+            if exec_data_ref.num_args() >= 5 {
+                let env_zval: &mut ZVal = exec_data_ref.get_mut_parameter(4);
+                if let Some(env) = env_zval.as_mut_z_arr() {
+                    for (name, value) in vars {
+                        env.insert(name.as_str(), value);
+                    }
+                    return;
+                }
+                if env_zval.get_type_info().is_null() {
+                    // null inherits the environment, which an array replaces
+                    let mut env = ZArray::new();
+                    // vars_os, since vars() panics on a variable which is not valid UTF-8 (those are skipped)
+                    let inherited = std::env::vars_os()
+                        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+                    for (name, value) in inherited.chain(vars) {
+                        env.insert(name.as_str(), value);
+                    }
+                    *env_zval = ZVal::from(env);
+                    return;
+                }
+            }
+        }
+        match command_line {
+            Some(_) if !cfg!(unix) => {
+                tracing::debug!("ExecHandler: not a POSIX shell, trace context not propagated");
+            }
+            Some(command_line) => {
+                let command_zval: &mut ZVal = exec_data_ref.get_mut_parameter(0);
+                *command_zval = ZVal::from(format!("{}{}", env_prefix(&vars), command_line));
+            }
+            None => tracing::debug!("ExecHandler: {} without env, trace context not propagated", function.name()),
+        }
+    }
+
+    unsafe extern "C-unwind" fn post_callback(
+        exec_data: *mut ExecuteData,
+        retval: &mut ZVal,
+        exception: Option<&mut ZObj>,
+        function: Function,
+    ) {
+        let _guard = take_guard(exec_data);
+        let context = Context::current();
+        let span_ref = context.span();
+        if let Some(exception) = exception {
+            record_exception(&context, exception);
+            return;
+        }
+        if retval.as_bool() == Some(false) {
+            span_ref.set_status(Status::error(format!("{} failed", function.name())));
+            return;
+        }
+        let exec_data_ref = unsafe {&mut *exec_data};
+        let Some(arg) = function.result_code_arg() else {
+            return;
+        };
+        if exec_data_ref.num_args() <= arg {
+            return;
+        }
+        let result_code_zval: &mut ZVal = exec_data_ref.get_mut_parameter(arg);
+        let exit_code = result_code_zval
+            .as_long()
+            .or_else(|| result_code_zval.as_z_ref().and_then(|reference| reference.val().as_long()));
+        if let Some(exit_code) = exit_code {
+            span_ref.set_attribute(KeyValue::new(SemConv::attribute::PROCESS_EXIT_CODE, exit_code));
+            if exit_code != 0 {
+                span_ref.set_status(Status::error(format!("exit code {}", exit_code)));
+            }
+        }
+    }
+}
+
+fn is_enabled(name: &str) -> bool {
+    config::env::var(name).is_ok_and(|value| value.trim().eq_ignore_ascii_case("true"))
+}
+
+fn base_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// The command name of a shell command line: the first word, skipping any variable assignments.
+fn command_name(command_line: &str) -> Option<String> {
+    command_line
+        .split_whitespace()
+        .find(|word| !word.contains('='))
+        .map(|word| base_name(word.trim_matches(|c| c == '\'' || c == '"')))
+}
+
+/// Environment variables for the injected carrier, in a fixed order.
+fn env_vars(carrier: &HashMap<String, String>) -> Vec<(String, String)> {
+    ENV_VARS
+        .iter()
+        .filter_map(|(key, name)| carrier.get(*key).map(|value| (name.to_string(), value.clone())))
+        .collect()
+}
+
+/// A POSIX shell prefix exporting the variables to the command (and everything it runs).
+fn env_prefix(vars: &[(String, String)]) -> String {
+    let assignments: Vec<String> = vars
+        .iter()
+        .map(|(name, value)| format!("{}='{}'", name, value.replace('\'', r"'\''")))
+        .collect();
+    format!("export {}; ", assignments.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_name() {
+        assert_eq!(command_name("/usr/bin/git status --short").as_deref(), Some("git"));
+        assert_eq!(command_name("LANG=C 'ls' -la").as_deref(), Some("ls"));
+        assert_eq!(command_name("  "), None);
+    }
+
+    #[test]
+    fn test_env_prefix() {
+        let carrier = HashMap::from([
+            ("traceparent".to_string(), "00-e77388f01a826e2de7afdcd1eefc034e-d6ba64af4fa59b65-01".to_string()),
+            ("baggage".to_string(), "user='x'".to_string()),
+        ]);
+        let vars = env_vars(&carrier);
+        assert_eq!(
+            env_prefix(&vars),
+            r"export TRACEPARENT='00-e77388f01a826e2de7afdcd1eefc034e-d6ba64af4fa59b65-01' BAGGAGE='user='\''x'\'''; "
+        );
+    }
+}
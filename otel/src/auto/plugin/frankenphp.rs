@@ -0,0 +1,105 @@
+use crate::{
+    auto::plugin::{Handler, HandlerList, HandlerSlice, HandlerCallbacks, Plugin},
+    request,
+};
+use phper::{
+    classes::{ClassEntity, ClassEntry, Visibility},
+    functions::Argument,
+    values::{
+        ExecuteData,
+        ZVal,
+    },
+};
+use std::sync::Arc;
+
+// FrankenPHP worker mode plugin. A worker script runs RINIT/RSHUTDOWN once, then handles many
+// requests by calling frankenphp_handle_request() in a loop. The callback passed to
+// frankenphp_handle_request is wrapped, so that each handled request gets its own environment and
+// root span. Requires PHP 8.2+, since frankenphp_handle_request is an internal function.
+
+const REQUEST_HANDLER_CLASS_NAME: &str = r"OpenTelemetry\Auto\FrankenPhp\RequestHandler";
+
+pub struct FrankenPhpPlugin {
+    handlers: HandlerList,
+}
+
+impl FrankenPhpPlugin {
+    pub fn new() -> Self {
+        Self {
+            handlers: vec![
+                Arc::new(HandleRequestHandler),
+            ],
+        }
+    }
+}
+
+impl Plugin for FrankenPhpPlugin {
+    fn get_handlers(&self) -> &HandlerSlice {
+        &self.handlers
+    }
+    fn get_name(&self) -> &str {
+        "frankenphp"
+    }
+}
+
+pub struct HandleRequestHandler;
+
+impl Handler for HandleRequestHandler {
+    fn get_targets(&self) -> Vec<(Option<&'static str>, &'static str)> {
+        vec![
+            (None, "frankenphp_handle_request"),
+        ]
+    }
+    fn get_callbacks(&self) -> HandlerCallbacks {
+        HandlerCallbacks {
+            pre_observe: Some(Box::new(|exec_data| unsafe {
+                Self::pre_callback(exec_data)
+            })),
+            post_observe: None,
+        }
+    }
+}
+
+impl HandleRequestHandler {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
+        let exec_data_ref = unsafe {&mut *exec_data};
+        if exec_data_ref.num_args() < 1 {
+            return;
+        }
+        let callback: &mut ZVal = exec_data_ref.get_mut_parameter(0);
+        let class_entry = match ClassEntry::from_globals(REQUEST_HANDLER_CLASS_NAME) {
+            Ok(class_entry) => class_entry,
+            Err(err) => {
+                tracing::warn!("FrankenPhpPlugin: request handler class not found: {:?}", err);
+                return;
+            }
+        };
+        match class_entry.new_object([callback.clone()]) {
+            Ok(wrapper) => *callback = ZVal::from(wrapper),
+            Err(err) => tracing::warn!("FrankenPhpPlugin: failed to wrap request callback: {:?}", err),
+        }
+    }
+}
+
+/// Invokable wrapper around a frankenphp_handle_request callback, which starts and ends a root
+/// span around each handled request.
+pub fn make_request_handler_class() -> ClassEntity<()> {
+    let mut class = ClassEntity::<()>::new(REQUEST_HANDLER_CLASS_NAME);
+    class.add_property("callback", Visibility::Private, ());
+
+    class.add_method("__construct", Visibility::Public, |this, arguments| {
+        this.set_property("callback", arguments[0].clone());
+        Ok::<_, phper::Error>(())
+    })
+        .argument(Argument::new("callback"));
+
+    class.add_method("__invoke", Visibility::Public, |this, arguments| {
+        let mut callback = this.get_property("callback").clone();
+        request::on_worker_request_init();
+        let result = callback.call(&mut *arguments);
+        request::on_worker_request_shutdown();
+        result
+    });
+
+    class
+}
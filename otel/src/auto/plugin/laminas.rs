@@ -116,7 +116,7 @@ impl Handler for LaminasApplicationRunHandler {
 }
 
 impl LaminasApplicationRunHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         match get_local_root_span_context() {
             Some(ctx) => {
                 ctx.span().set_attribute(KeyValue::new(trace_attributes::PHP_FRAMEWORK_NAME, "laminas"));
@@ -128,7 +128,7 @@ impl LaminasApplicationRunHandler {
         let span_name = "Application::run".to_string();
         utils::start_and_activate_span(tracer, &span_name, vec![], exec_data, opentelemetry::trace::SpanKind::Internal);
     }
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         _exception: Option<&mut ZObj>
@@ -157,7 +157,7 @@ impl Handler for LaminasCompleteRequestHandler {
 }
 
 impl LaminasCompleteRequestHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         //get the first argument from exec_data, which is an MvcEvent
         let exec_data_ref = unsafe { &mut *exec_data };
         let mvc_event_zval: &mut ZVal = exec_data_ref.get_mut_parameter(0);
@@ -217,7 +217,7 @@ impl Handler for LaminasRouteHandler {
 }
 
 impl LaminasRouteHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         tracing::debug!("Auto::Laminas::pre (MvcEvent::setRouteMatch)");
         let ctx = match get_local_root_span_context() {
             Some(ctx) => ctx,
@@ -297,7 +297,7 @@ fn extract_laminas_db_namespace(arr: &phper::arrays::ZArr) -> String {
 }
 
 impl LaminasDbConnectHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         utils::start_and_activate_span(
             tracer_provider::get_tracer_provider().tracer("php.otel.auto.laminas.db"),
             "connect",
@@ -338,7 +338,7 @@ impl LaminasDbConnectHandler {
         }
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -371,7 +371,7 @@ impl Handler for LaminasStatementPrepareHandler {
 }
 
 impl LaminasStatementPrepareHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         utils::start_and_activate_span(
             tracer_provider::get_tracer_provider().tracer("php.otel.auto.laminas.db"),
             "prepare",
@@ -380,7 +380,7 @@ impl LaminasStatementPrepareHandler {
             opentelemetry::trace::SpanKind::Client
         );
     }
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -485,14 +485,14 @@ impl Handler for LaminasStatementExecuteHandler {
 }
 
 impl LaminasStatementExecuteHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         tracing::debug!("Auto::Laminas::pre (Statement::execute) - pre_callback called");
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.laminas.db");
         let span_name = "Statement::execute".to_string();
         utils::start_and_activate_span(tracer, &span_name, vec![], exec_data, SpanKind::Client);
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -544,7 +544,7 @@ impl Handler for LaminasConnectionExecuteHandler {
 }
 
 impl LaminasConnectionExecuteHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         tracing::debug!("Auto::Laminas::pre (Connection::execute) - pre_callback called");
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.laminas.db");
         let exec_data_ref = unsafe {&mut *exec_data};
@@ -560,7 +560,7 @@ impl LaminasConnectionExecuteHandler {
         utils::start_and_activate_span(tracer, &span_name, attributes, exec_data, opentelemetry::trace::SpanKind::Client);
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -3,6 +3,7 @@ use crate::{
         plugin::{Handler, HandlerList, HandlerSlice, HandlerCallbacks, Plugin},
         utils::{start_and_activate_span, record_exception},
     },
+    config::http_headers::{HeaderCapture, Side},
     context::storage::{take_guard},
     trace::tracer_provider,
 };
@@ -73,7 +74,7 @@ impl Handler for Psr18SendRequestHandler {
 }
 
 impl Psr18SendRequestHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.psr18");
         let mut name = "psr18.request".to_string();
 
@@ -114,6 +115,13 @@ impl Psr18SendRequestHandler {
                     name = method_str.to_string();
                 }
             }
+            let header_capture = HeaderCapture::from_config(Side::Client);
+            for header in &header_capture.request {
+                let values = get_header_values(request_obj, header);
+                if !values.is_empty() {
+                    attributes.push(header_capture.request_attribute(header, values));
+                }
+            }
         }
 
         start_and_activate_span(tracer, &name, attributes, exec_data, SpanKind::Client);
@@ -136,7 +144,7 @@ impl Psr18SendRequestHandler {
         *request_zval = modified_request;
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -175,5 +183,26 @@ impl Psr18SendRequestHandler {
 
         span_ref.set_attribute(KeyValue::new(SemConv::trace::HTTP_RESPONSE_STATUS_CODE, status_code));
 
+        let header_capture = HeaderCapture::from_config(Side::Client);
+        for header in &header_capture.response {
+            let values = get_header_values(response_obj, header);
+            if !values.is_empty() {
+                span_ref.set_attribute(header_capture.response_attribute(header, values));
+            }
+        }
     }
 }
+
+/// Values of a PSR-7 message header (MessageInterface::getHeader returns string[])
+fn get_header_values(message: &mut ZObj, name: &str) -> Vec<String> {
+    message.call("getHeader", &mut [ZVal::from(name)])
+        .ok()
+        .and_then(|values| {
+            values.as_z_arr().map(|values| {
+                values.iter()
+                    .filter_map(|(_, value)| value.as_z_str()?.to_str().ok().map(|s| s.to_owned()))
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
@@ -1,6 +1,7 @@
 // A test plugin which implements three handlers:
 // - DemoHandler: observes a handful of classes and functions with a pre and post callback
 // - DemoFunctionHandler: observes a specific function with a different pre and post callback
+// - DemoPanicHandler: observes a specific function with a pre callback which panics
 use crate::{
     auto::{
         execute_data::get_fqn,
@@ -35,6 +36,7 @@ impl TestPlugin {
                 Arc::new(DemoFunctionHandler),
                 Arc::new(DemoHelloHandler),
                 Arc::new(TestClassHandler),
+                Arc::new(DemoPanicHandler),
             ],
         }
     }
@@ -60,6 +62,7 @@ impl Handler for DemoHandler {
             (Some("DemoClass"), "test"),
             (Some("DemoClass"), "inner"),
             (None, "phpversion"),
+            (None, "demoGenerator"),
             (Some("IDemo"), "foo"),
             (Some("IDemo"), "bar"),
         ]
@@ -77,7 +80,7 @@ impl Handler for DemoHandler {
 }
 
 impl DemoHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.test");
         let exec_data_ref = unsafe { &*exec_data };
         let span_name = get_fqn(exec_data_ref).unwrap_or_default();
@@ -85,7 +88,7 @@ impl DemoHandler {
         utils::start_and_activate_span(tracer, &span_name, vec![], exec_data, opentelemetry::trace::SpanKind::Internal);
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         _exception: Option<&mut ZObj>
@@ -113,7 +116,7 @@ impl Handler for DemoHelloHandler {
 }
 
 impl DemoHelloHandler {
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         _exec_data: *mut ExecuteData,
         retval: &mut ZVal,
         _exception: Option<&mut ZObj>
@@ -150,7 +153,7 @@ impl Handler for DemoFunctionHandler {
 }
 
 impl DemoFunctionHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.test");
         let mut attributes = vec![];
         attributes.push(KeyValue::new("my-attribute", "my-value".to_string()));
@@ -159,7 +162,7 @@ impl DemoFunctionHandler {
         utils::start_and_activate_span(tracer, &span_name, attributes, exec_data, opentelemetry::trace::SpanKind::Internal);
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -197,11 +200,11 @@ impl Handler for TestClassHandler {
 }
 
 impl TestClassHandler {
-    unsafe extern "C" fn pre_callback(_exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(_exec_data: *mut ExecuteData) {
         tracing::debug!("TestClassHandler: pre_callback called");
     }
 
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         _exec_data: *mut ExecuteData,
         retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -210,4 +213,27 @@ impl TestClassHandler {
         tracing::debug!("retval type: {:?}", retval.get_type_info());
         tracing::debug!("exception: {:?}", exception);
     }
-}
\ No newline at end of file
+}
+pub struct DemoPanicHandler;
+
+impl Handler for DemoPanicHandler {
+    fn get_targets(&self) -> Vec<(Option<&'static str>, &'static str)> {
+        vec![
+            (None, "demoPanic"),
+        ]
+    }
+    fn get_callbacks(&self) -> HandlerCallbacks {
+        HandlerCallbacks {
+            pre_observe: Some(Box::new(|exec_data| unsafe {
+                Self::pre_callback(exec_data)
+            })),
+            post_observe: None,
+        }
+    }
+}
+
+impl DemoPanicHandler {
+    unsafe extern "C-unwind" fn pre_callback(_exec_data: *mut ExecuteData) {
+        panic!("DemoPanicHandler: pre_callback panicked");
+    }
+}
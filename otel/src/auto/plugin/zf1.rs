@@ -109,7 +109,7 @@ impl Handler for Zf1RouteHandler {
 }
 
 impl Zf1RouteHandler {
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         retval: &mut ZVal,
         _exception: Option<&mut ZObj>
@@ -198,7 +198,7 @@ impl Handler for Zf1SendResponseHandler {
 }
 
 impl Zf1SendResponseHandler {
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         _exception: Option<&mut ZObj>
@@ -269,7 +269,7 @@ impl Handler for Zf1AdapterConnectHandler {
 }
 
 impl Zf1AdapterConnectHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.zf1.db");
         let exec_data_ref = unsafe {&mut *exec_data};
         let this_obj = exec_data_ref.get_this_mut().unwrap();
@@ -316,7 +316,7 @@ impl Zf1AdapterConnectHandler {
         tracing::debug!("Zf1AdapterConnectHandler: should_start_span: {}", should_start_span);
         execute_data::set_exec_data_flag(exec_data, should_start_span);
     }
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -355,7 +355,7 @@ impl Handler for Zf1AdapterPrepareHandler {
 }
 
 impl Zf1AdapterPrepareHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.zf1.db");
         let exec_data_ref = unsafe {&mut *exec_data};
         let mut span_name = "prepare".to_string();
@@ -375,7 +375,7 @@ impl Zf1AdapterPrepareHandler {
 
         utils::start_and_activate_span(tracer, &span_name, attributes, exec_data, opentelemetry::trace::SpanKind::Client);
     }
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         retval: &mut ZVal,
         exception: Option<&mut ZObj>
@@ -454,7 +454,7 @@ impl Handler for Zf1StatementExecuteHandler {
 }
 
 impl Zf1StatementExecuteHandler {
-    unsafe extern "C" fn pre_callback(exec_data: *mut ExecuteData) {
+    unsafe extern "C-unwind" fn pre_callback(exec_data: *mut ExecuteData) {
         let tracer = tracer_provider::get_tracer_provider().tracer("php.otel.auto.zf1.db");
         let exec_data_ref = unsafe { &mut *exec_data };
         let mut attributes = vec![];
@@ -478,7 +478,7 @@ impl Zf1StatementExecuteHandler {
                 .add_link(link.unwrap(), vec![]);
         }
     }
-    unsafe extern "C" fn post_callback(
+    unsafe extern "C-unwind" fn post_callback(
         exec_data: *mut ExecuteData,
         _retval: &mut ZVal,
         exception: Option<&mut ZObj>
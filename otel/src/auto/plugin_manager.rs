@@ -1,14 +1,18 @@
 use crate::{
     auto::{
+        circuit_breaker,
         execute_data::get_fqn,
         plugin::{FunctionObserver, Plugin},
         plugin::{
+            exec::ExecPlugin,
+            frankenphp::FrankenPhpPlugin,
             laminas::LaminasPlugin,
             psr18::Psr18Plugin,
             zf1::Zf1Plugin,
         },
     },
     config,
+    util::get_sapi_module_name,
 };
 use phper::{
     classes::ClassEntry,
@@ -56,12 +60,19 @@ impl PluginManager {
     pub fn request_shutdown(&self) {
         tracing::debug!("PluginManager::request_shutdown");
         for plugin in &self.plugins {
-            plugin.request_shutdown();
+            circuit_breaker::run_hook(plugin.get_name(), || plugin.request_shutdown());
         }
     }
 
     fn init(&mut self) {
         let disabled = get_disabled_plugins();
+        if !disabled.contains("exec") {
+            self.plugins.push(Box::new(ExecPlugin::new()));
+        }
+        // frankenphp_handle_request only exists in FrankenPHP
+        if !disabled.contains("frankenphp") && get_sapi_module_name() == "frankenphp" {
+            self.plugins.push(Box::new(FrankenPhpPlugin::new()));
+        }
         if !disabled.contains("laminas") {
             self.plugins.push(Box::new(LaminasPlugin::new()));
         }
@@ -99,13 +110,15 @@ impl PluginManager {
                 if should_trace(execute_data.func(), &handler.get_targets(), plugin.get_name()) {
                     let callbacks = handler.get_callbacks();
                     if let Some(pre) = callbacks.pre_observe {
+                        let plugin_name = plugin.get_name().to_string();
                         observer.add_pre_hook(Box::new(move |execute_data| {
-                            pre(execute_data);
+                            circuit_breaker::run_hook(&plugin_name, || pre(execute_data));
                         }));
                     }
                     if let Some(post) = callbacks.post_observe {
+                        let plugin_name = plugin.get_name().to_string();
                         observer.add_post_hook(Box::new(move |execute_data, retval, exception| {
-                            post(execute_data, retval, exception);
+                            circuit_breaker::run_hook(&plugin_name, || post(execute_data, retval, exception));
                         }));
                     }
                 }
@@ -1,5 +1,6 @@
 use phper::modules::Module;
 use crate::{
+    auto::plugin::frankenphp::make_request_handler_class,
     context::{
         context::{build_context_class, new_context_class},
         context_interface::make_context_interface,
@@ -76,4 +77,5 @@ pub fn register_classes_and_interfaces(module: &mut Module) {
     let _status_code_interface = module.add_interface(make_status_code_interface());
 
     let _log_record_class = module.add_class(make_log_record_class());
+    let _frankenphp_request_handler_class = module.add_class(make_request_handler_class());
 }
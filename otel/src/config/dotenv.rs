@@ -0,0 +1,202 @@
+// Discovery and parsing of per-app .env files, cached per worker. Entries are keyed by script
+// directory (and DOCUMENT_ROOT), and trusted without any filesystem access until the TTL expires.
+// After that, discovery is repeated, and the file is only re-parsed if its path, inode or mtime
+// has changed.
+use crate::config;
+use once_cell::sync::Lazy;
+use phper::ini::ini_get;
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A discovered .env file, and the OTEL_* variables it contains.
+pub struct Dotenv {
+    pub path: PathBuf,
+    pub vars: Vec<(String, String)>,
+    file_id: FileId,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct FileId {
+    inode: u64,
+    mtime: Option<SystemTime>,
+}
+
+struct CacheEntry {
+    dotenv: Option<Arc<Dotenv>>,
+    checked_at: Instant,
+}
+
+pub struct DotenvCache {
+    entries: HashMap<(PathBuf, Option<String>), CacheEntry>,
+}
+
+static DOTENV_CACHE: Lazy<Mutex<DotenvCache>> = Lazy::new(|| Mutex::new(DotenvCache::new()));
+
+/// Find and parse the .env file for a script, using the worker's cache.
+pub fn load(script_filename: &str, document_root: Option<&str>) -> Option<Arc<Dotenv>> {
+    let ttl = ini_get::<i64>(config::ini::OTEL_ENV_DOTENV_CACHE_TTL).max(0) as u64;
+    let mut cache = match DOTENV_CACHE.lock() {
+        Ok(cache) => cache,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    cache.get(script_filename, document_root, Duration::from_secs(ttl), Instant::now())
+}
+
+/// Number of cached script directories, for phpinfo()
+pub fn cache_size() -> usize {
+    DOTENV_CACHE.lock().map(|cache| cache.entries.len()).unwrap_or(0)
+}
+
+impl DotenvCache {
+    pub fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+
+    pub fn get(&mut self, script_filename: &str, document_root: Option<&str>, ttl: Duration, now: Instant) -> Option<Arc<Dotenv>> {
+        let script_dir = Path::new(script_filename).parent()?.to_path_buf();
+        let key = (script_dir, document_root.map(str::to_string));
+        if let Some(entry) = self.entries.get(&key) {
+            if now.duration_since(entry.checked_at) < ttl {
+                tracing::trace!("Using cached .env for {:?}", key.0);
+                return entry.dotenv.clone();
+            }
+        }
+        let found = find_dotenv(&key.0, document_root).and_then(|path| {
+            let file_id = get_file_id(&path)?;
+            Some((path, file_id))
+        });
+        let previous = self.entries.remove(&key).and_then(|entry| entry.dotenv);
+        let dotenv = match (found, previous) {
+            (Some((path, file_id)), Some(previous)) if previous.path == path && previous.file_id == file_id => {
+                tracing::trace!("Cached .env {:?} is unchanged", path);
+                Some(previous)
+            }
+            (Some((path, file_id)), _) => {
+                tracing::debug!("Parsing .env {:?}", path);
+                Some(Arc::new(Dotenv {
+                    vars: parse_dotenv(&path),
+                    path,
+                    file_id,
+                }))
+            }
+            (None, _) => None,
+        };
+        self.entries.insert(key, CacheEntry { dotenv: dotenv.clone(), checked_at: now });
+        dotenv
+    }
+}
+
+fn get_file_id(path: &Path) -> Option<FileId> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(FileId {
+        inode: metadata.ino(),
+        mtime: metadata.modified().ok(),
+    })
+}
+
+/// Parse OTEL_* variables from a .env file. Other variables (app secrets etc) are ignored.
+fn parse_dotenv(path: &Path) -> Vec<(String, String)> {
+    match dotenvy::from_path_iter(path) {
+        Ok(iter) => iter
+            .flatten()
+            .filter(|(k, _)| k.starts_with("OTEL_"))
+            .collect(),
+        Err(err) => {
+            tracing::warn!("Failed to read .env {:?}: {}", path, err);
+            Vec::new()
+        }
+    }
+}
+
+/// Find the closest .env file, from the script's directory up to DOCUMENT_ROOT.
+fn find_dotenv(script_dir: &Path, document_root: Option<&str>) -> Option<PathBuf> {
+    let env_in_dir = |dir: &Path| {
+        let env_path = dir.join(".env");
+        fs::metadata(&env_path).ok().map(|_| env_path)
+    };
+
+    match document_root {
+        Some(document_root) => {
+            if document_root.is_empty() {
+                return env_in_dir(script_dir);
+            }
+            let docroot = match Path::new(document_root).canonicalize() {
+                Ok(path) => path,
+                Err(err) => {
+                    tracing::warn!("Failed to canonicalize DOCUMENT_ROOT '{}': {}", document_root, err);
+                    return env_in_dir(script_dir);
+                },
+            };
+            let mut current = match script_dir.canonicalize() {
+                Ok(path) => path,
+                Err(_) => return None,
+            };
+            loop {
+                if let Some(env_path) = env_in_dir(&current) {
+                    return Some(env_path);
+                }
+                if current == docroot {
+                    break;
+                }
+                if !current.pop() {
+                    break;
+                }
+            }
+            None
+        }
+        None => env_in_dir(script_dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_app(name: &str, contents: &str) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("otel-dotenv-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(".env"), contents).unwrap();
+        let script = dir.join("index.php").to_string_lossy().to_string();
+        (dir, script)
+    }
+
+    #[test]
+    fn test_parses_otel_vars_only() {
+        let (dir, script) = temp_app("parse", "OTEL_SERVICE_NAME=app\nDB_PASSWORD=secret\n");
+        let mut cache = DotenvCache::new();
+        let dotenv = cache.get(&script, None, Duration::from_secs(60), Instant::now()).unwrap();
+        assert_eq!(dotenv.vars, vec![("OTEL_SERVICE_NAME".to_string(), "app".to_string())]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cached_within_ttl() {
+        let (dir, script) = temp_app("ttl", "OTEL_SERVICE_NAME=app\n");
+        let mut cache = DotenvCache::new();
+        let now = Instant::now();
+        let first = cache.get(&script, None, Duration::from_secs(60), now).unwrap();
+        fs::remove_file(dir.join(".env")).unwrap();
+        let second = cache.get(&script, None, Duration::from_secs(60), now + Duration::from_secs(1)).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        // after the TTL, the removal is noticed
+        assert!(cache.get(&script, None, Duration::from_secs(60), now + Duration::from_secs(61)).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unchanged_file_not_reparsed() {
+        let (dir, script) = temp_app("unchanged", "OTEL_SERVICE_NAME=app\n");
+        let mut cache = DotenvCache::new();
+        let now = Instant::now();
+        let first = cache.get(&script, None, Duration::ZERO, now).unwrap();
+        let second = cache.get(&script, None, Duration::ZERO, now).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+// Request-scoped OTEL_* environment variables, set from $_SERVER or a .env file.
+// Values are stored per thread so that concurrent requests in a threaded (ZTS) SAPI cannot see
+// each other's configuration. For non-ZTS builds, they are also written to the process
+// environment, so that they are visible to getenv() and to opentelemetry-rust.
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    env::{self, VarError},
+};
+
+thread_local! {
+    static REQUEST_ENV: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+/// Get an environment variable, preferring a request-scoped value over the process environment.
+pub fn var(key: &str) -> Result<String, VarError> {
+    match REQUEST_ENV.with(|cell| cell.borrow().get(key).cloned()) {
+        Some(value) => Ok(value),
+        None => env::var(key),
+    }
+}
+
+/// Set a request-scoped environment variable.
+pub fn set_var(key: &str, value: &str) {
+    REQUEST_ENV.with(|cell| {
+        cell.borrow_mut().insert(key.to_string(), value.to_string());
+    });
+    #[cfg(not(otel_zts))]
+    {
+        unsafe { env::set_var(key, value) };
+    }
+}
+
+/// Discard all request-scoped variables.
+pub fn clear() {
+    REQUEST_ENV.with(|cell| cell.borrow_mut().clear());
+}
+
+/// Run `f` with only the given request-scoped variables, which (unlike set_var) are not written to
+/// the process environment, so that tests running in parallel do not see each other's.
+#[cfg(test)]
+pub fn with_request_vars<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    REQUEST_ENV.with(|cell| {
+        *cell.borrow_mut() = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    });
+    let result = f();
+    clear();
+    result
+}
+
+/// Get all variables with a prefix, request-scoped values taking precedence.
+pub fn vars_with_prefix(prefix: &str) -> BTreeMap<String, String> {
+    let mut vars: BTreeMap<String, String> = env::vars()
+        .filter(|(k, _)| k.starts_with(prefix))
+        .collect();
+    REQUEST_ENV.with(|cell| {
+        for (k, v) in cell.borrow().iter().filter(|(k, _)| k.starts_with(prefix)) {
+            vars.insert(k.clone(), v.clone());
+        }
+    });
+    vars
+}
+
+/// Key for the effective SDK configuration, used to cache a tracer/logger provider per app.
+/// Service name and resource attributes are kept readable, the remaining OTEL_* variables (which
+/// may contain secrets, eg exporter headers) are hashed.
+pub fn provider_key() -> String {
+    let service_name = var("OTEL_SERVICE_NAME").unwrap_or_default();
+    let resource_attrs = var("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default();
+    format!("{}:{}:{}", service_name, resource_attrs, hash_vars(is_sdk_config))
+}
+
+/// Key for the effective span export configuration (exporter, endpoint, headers, batching, spool),
+/// so that providers which only differ by resource can share an export pipeline.
+pub fn exporter_key() -> String {
+    hash_vars(|k| {
+        k.starts_with("OTEL_EXPORTER_")
+            || k.starts_with("OTEL_BSP_")
+            || k.starts_with("OTEL_PHP_SPOOL_")
+            || k == "OTEL_TRACES_EXPORTER"
+    })
+}
+
+/// Key for the destination of exported batches (exporter, protocol, endpoint and headers), naming
+/// the retry spool directory. It is persisted, so must not change with settings which do not affect
+/// where a batch is sent (batching, spool size), nor between builds.
+pub fn spool_key() -> String {
+    hash_vars(|k| {
+        k == "OTEL_TRACES_EXPORTER"
+            || (k.starts_with("OTEL_EXPORTER_OTLP_")
+                && (k.ends_with("ENDPOINT") || k.ends_with("HEADERS") || k.ends_with("PROTOCOL")))
+    })
+}
+
+/// FNV-1a hash of the included variables. Unlike std's DefaultHasher, the result is stable across
+/// Rust releases.
+fn hash_vars<F: Fn(&str) -> bool>(include: F) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (k, v) in vars_with_prefix("OTEL_") {
+        if include(&k) {
+            for byte in k.bytes().chain([b'=']).chain(v.bytes()).chain([b'\n']) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Whether a variable configures the SDK (and so needs a separate provider), rather than being
+/// part of the readable key, or per-request behaviour of this extension.
+fn is_sdk_config(key: &str) -> bool {
+    !matches!(key, "OTEL_SERVICE_NAME" | "OTEL_RESOURCE_ATTRIBUTES" | "OTEL_SDK_DISABLED")
+        && (!key.starts_with("OTEL_PHP_") || key.starts_with("OTEL_PHP_SPOOL_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_request_var_overrides_process_env() {
+        unsafe { env::set_var("OTEL_TEST_ENV_OVERRIDE", "process") };
+        assert_eq!(var("OTEL_TEST_ENV_OVERRIDE").as_deref(), Ok("process"));
+        with_request_vars(&[("OTEL_TEST_ENV_OVERRIDE", "request")], || {
+            assert_eq!(var("OTEL_TEST_ENV_OVERRIDE").as_deref(), Ok("request"));
+            assert_eq!(vars_with_prefix("OTEL_TEST_ENV_OVERRIDE").get("OTEL_TEST_ENV_OVERRIDE").map(String::as_str), Some("request"));
+        });
+        assert_eq!(var("OTEL_TEST_ENV_OVERRIDE").as_deref(), Ok("process"));
+        unsafe { env::remove_var("OTEL_TEST_ENV_OVERRIDE") };
+    }
+
+    #[test]
+    fn test_request_vars_are_per_thread() {
+        with_request_vars(&[("OTEL_TEST_ENV_THREAD", "main")], || {
+            let other = thread::spawn(|| {
+                let before = var("OTEL_TEST_ENV_THREAD").ok();
+                with_request_vars(&[("OTEL_TEST_ENV_THREAD", "other")], || var("OTEL_TEST_ENV_THREAD").ok())
+                    .map(|during| (before, during))
+            })
+            .join()
+            .unwrap();
+            assert_eq!(other, Some((None, "other".to_string())));
+            assert_eq!(var("OTEL_TEST_ENV_THREAD").as_deref(), Ok("main"));
+        });
+    }
+
+    #[test]
+    fn test_clear() {
+        REQUEST_ENV.with(|cell| cell.borrow_mut().insert("OTEL_TEST_ENV_CLEAR".to_string(), "value".to_string()));
+        assert!(var("OTEL_TEST_ENV_CLEAR").is_ok());
+        clear();
+        assert!(var("OTEL_TEST_ENV_CLEAR").is_err());
+    }
+
+    #[test]
+    fn test_provider_key_per_request() {
+        let key = |vars: &[(&str, &str)]| with_request_vars(vars, provider_key);
+        let first = key(&[("OTEL_SERVICE_NAME", "first")]);
+        assert!(first.starts_with("first::"));
+        assert_ne!(first, key(&[("OTEL_SERVICE_NAME", "second")]));
+        assert_ne!(first, key(&[("OTEL_SERVICE_NAME", "first"), ("OTEL_EXPORTER_OTLP_HEADERS", "api-key=secret")]));
+        assert!(!key(&[("OTEL_SERVICE_NAME", "first"), ("OTEL_EXPORTER_OTLP_HEADERS", "api-key=secret")]).contains("secret"));
+        assert_eq!(first, key(&[("OTEL_SERVICE_NAME", "first"), ("OTEL_PHP_EXCLUDED_METHODS", "HEAD")]));
+    }
+
+    #[test]
+    fn test_spool_key() {
+        let key = |vars: &[(&str, &str)]| with_request_vars(vars, spool_key);
+        let endpoint = ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318");
+        let first = key(&[endpoint]);
+        // stable between builds, as the key names a directory of spooled batches
+        assert_eq!(first, "1ca941c4559f7789");
+        assert_eq!(first, key(&[endpoint, ("OTEL_BSP_MAX_QUEUE_SIZE", "100"), ("OTEL_PHP_SPOOL_MAX_SIZE", "10")]));
+        assert_eq!(first, key(&[endpoint, ("OTEL_EXPORTER_OTLP_TIMEOUT", "1000"), ("OTEL_SERVICE_NAME", "other")]));
+        assert_ne!(first, key(&[endpoint, ("OTEL_EXPORTER_OTLP_HEADERS", "api-key=secret")]));
+        assert_ne!(first, key(&[endpoint, ("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL", "grpc")]));
+        assert_ne!(first, key(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://other:4318")]));
+    }
+}
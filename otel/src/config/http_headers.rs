@@ -0,0 +1,130 @@
+// HTTP header capture, configured by OTEL_INSTRUMENTATION_HTTP_{SERVER,CLIENT}_CAPTURE_{REQUEST,
+// RESPONSE}_HEADERS (comma-separated, case-insensitive header names). Captured headers become
+// http.request.header.<name> / http.response.header.<name> string array attributes. Values of
+// headers in OTEL_INSTRUMENTATION_HTTP_CAPTURE_HEADERS_SANITIZE_FIELDS (default: credentials and
+// cookies) are replaced with [REDACTED].
+use crate::config;
+use opentelemetry::{Array, KeyValue, StringValue, Value};
+use opentelemetry_semantic_conventions as SemConv;
+
+const DEFAULT_SANITIZE_FIELDS: &str = "authorization,proxy-authorization,cookie,set-cookie,x-api-key,x-auth-token";
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Copy, Debug)]
+pub enum Side {
+    Server,
+    Client,
+}
+
+impl Side {
+    fn env_name(&self) -> &'static str {
+        match self {
+            Side::Server => "SERVER",
+            Side::Client => "CLIENT",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HeaderCapture {
+    pub request: Vec<String>,
+    pub response: Vec<String>,
+    sanitize: Vec<String>,
+}
+
+fn parse_names(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+impl HeaderCapture {
+    pub fn from_config(side: Side) -> Self {
+        let get = |kind: &str| {
+            config::env::var(&format!("OTEL_INSTRUMENTATION_HTTP_{}_CAPTURE_{}_HEADERS", side.env_name(), kind))
+                .map(|list| parse_names(&list))
+                .unwrap_or_default()
+        };
+        let sanitize = config::env::var("OTEL_INSTRUMENTATION_HTTP_CAPTURE_HEADERS_SANITIZE_FIELDS")
+            .unwrap_or_else(|_| DEFAULT_SANITIZE_FIELDS.to_string());
+        Self::new(get("REQUEST"), get("RESPONSE"), parse_names(&sanitize))
+    }
+
+    pub fn new(request: Vec<String>, response: Vec<String>, sanitize: Vec<String>) -> Self {
+        Self { request, response, sanitize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.request.is_empty() && self.response.is_empty()
+    }
+
+    fn attribute(&self, prefix: &str, name: &str, values: Vec<String>) -> KeyValue {
+        let name = name.to_ascii_lowercase();
+        let values = if self.sanitize.contains(&name) {
+            values.iter().map(|_| StringValue::from(REDACTED)).collect()
+        } else {
+            values.into_iter().map(StringValue::from).collect()
+        };
+        KeyValue::new(format!("{}.{}", prefix, name), Value::Array(Array::String(values)))
+    }
+
+    /// http.request.header.<name> attribute for a captured request header.
+    pub fn request_attribute(&self, name: &str, values: Vec<String>) -> KeyValue {
+        self.attribute(SemConv::trace::HTTP_REQUEST_HEADER, name, values)
+    }
+
+    /// http.response.header.<name> attribute for a captured response header.
+    pub fn response_attribute(&self, name: &str, values: Vec<String>) -> KeyValue {
+        self.attribute(SemConv::trace::HTTP_RESPONSE_HEADER, name, values)
+    }
+
+    /// Attributes for the captured response headers, from raw "Name: value" header lines.
+    pub fn response_attributes_from_lines<'a>(&self, lines: impl Iterator<Item = &'a str>) -> Vec<KeyValue> {
+        let mut captured: Vec<(&str, Vec<String>)> = self.response.iter().map(|name| (name.as_str(), Vec::new())).collect();
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let name = name.trim();
+            if let Some((_, values)) = captured.iter_mut().find(|(captured, _)| captured.eq_ignore_ascii_case(name)) {
+                values.push(value.trim().to_string());
+            }
+        }
+        captured
+            .into_iter()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(name, values)| self.response_attribute(name, values))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitizes_values() {
+        let capture = HeaderCapture::new(vec![], vec![], parse_names(DEFAULT_SANITIZE_FIELDS));
+        let attribute = capture.request_attribute("Authorization", vec!["Bearer secret".to_string()]);
+        assert_eq!(attribute.key.as_str(), "http.request.header.authorization");
+        assert_eq!(attribute.value, Value::Array(Array::String(vec![StringValue::from(REDACTED)])));
+        let attribute = capture.request_attribute("X-Request-Id", vec!["abc".to_string()]);
+        assert_eq!(attribute.value, Value::Array(Array::String(vec![StringValue::from("abc")])));
+    }
+
+    #[test]
+    fn test_response_attributes_from_lines() {
+        let capture = HeaderCapture::new(vec![], parse_names("Content-Type, set-cookie, x-missing"), parse_names("set-cookie"));
+        let lines = ["Content-Type: text/html", "Set-Cookie: a=1", "set-cookie: b=2", "X-Other: 1"];
+        let attributes = capture.response_attributes_from_lines(lines.into_iter());
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].key.as_str(), "http.response.header.content-type");
+        assert_eq!(attributes[0].value, Value::Array(Array::String(vec![StringValue::from("text/html")])));
+        assert_eq!(attributes[1].key.as_str(), "http.response.header.set-cookie");
+        assert_eq!(
+            attributes[1].value,
+            Value::Array(Array::String(vec![StringValue::from(REDACTED), StringValue::from(REDACTED)]))
+        );
+    }
+}
@@ -2,7 +2,18 @@ pub const OTEL_LOG_LEVEL: &str = "otel.log.level";
 pub const OTEL_LOG_FILE: &str = "otel.log.file";
 pub const OTEL_CLI_CREATE_ROOT_SPAN: &str = "otel.cli.create_root_span";
 pub const OTEL_CLI_ENABLED: &str = "otel.cli.enabled";
+pub const OTEL_CLI_FLUSH_INTERVAL: &str = "otel.cli.flush_interval";
 pub const OTEL_AUTO_ENABLED: &str = "otel.auto.enabled";
 pub const OTEL_AUTO_DISABLED_PLUGINS: &str = "otel.auto.disabled_plugins";
 pub const OTEL_ENV_SET_FROM_SERVER: &str = "otel.env.set_from_server";
 pub const OTEL_ENV_DOTENV_ENABLED: &str = "otel.env.dotenv.enabled";
+pub const OTEL_ENV_DOTENV_CACHE_TTL: &str = "otel.env.dotenv.cache_ttl";
+pub const OTEL_AUTO_PLUGIN_FAILURE_THRESHOLD: &str = "otel.auto.plugin_failure_threshold";
+pub const OTEL_PROVIDER_CACHE_MAX_SIZE: &str = "otel.provider_cache.max_size";
+pub const OTEL_PROVIDER_CACHE_IDLE_TIMEOUT: &str = "otel.provider_cache.idle_timeout";
+pub const OTEL_AGENT_SOCKET: &str = "otel.agent.socket";
+pub const OTEL_SPOOL_DIR: &str = "otel.spool.dir";
+pub const OTEL_SPOOL_MAX_SIZE: &str = "otel.spool.max_size";
+pub const OTEL_TRACES_SHARED_PIPELINE: &str = "otel.traces.shared_pipeline";
+pub const OTEL_HTTP_ROUTES: &str = "otel.http.routes";
+pub const OTEL_HTTP_ROUTE_SCRIPT_FALLBACK: &str = "otel.http.route_script_fallback";
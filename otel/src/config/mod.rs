@@ -1,2 +1,6 @@
+pub mod dotenv;
+pub mod env;
+pub mod http_headers;
 pub mod ini;
+pub mod otlp;
 pub mod trace_attributes;
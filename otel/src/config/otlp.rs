@@ -0,0 +1,377 @@
+// OTLP exporter configuration, read from the request-scoped environment (see config::env) and
+// applied explicitly to exporter builders. The exporters also read the process environment
+// themselves, but with ZTS that does not contain request-scoped (.env or $_SERVER) values.
+use crate::config;
+use opentelemetry_otlp::{Compression, Protocol};
+use tonic::{
+    metadata::{Ascii, MetadataKey, MetadataMap, MetadataValue},
+    transport::{Certificate, ClientTlsConfig, Identity},
+};
+use std::{collections::HashMap, fs, time::Duration};
+
+/// Export timeout when OTEL_EXPORTER_OTLP_TIMEOUT is not set
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Signal {
+    Traces,
+    Logs,
+}
+
+impl Signal {
+    fn env_name(&self) -> &'static str {
+        match self {
+            Signal::Traces => "TRACES",
+            Signal::Logs => "LOGS",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Signal::Traces => "traces",
+            Signal::Logs => "logs",
+        }
+    }
+
+    pub fn http_path(&self) -> &'static str {
+        match self {
+            Signal::Traces => "/v1/traces",
+            Signal::Logs => "/v1/logs",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OtlpProtocol {
+    Grpc,
+    HttpProtobuf,
+    HttpJson,
+}
+
+impl OtlpProtocol {
+    pub fn is_http(&self) -> bool {
+        *self != OtlpProtocol::Grpc
+    }
+
+    /// Protocol for the opentelemetry-otlp http exporter builders
+    pub fn http_protocol(&self) -> Protocol {
+        match self {
+            OtlpProtocol::HttpJson => Protocol::HttpJson,
+            _ => Protocol::HttpBinary,
+        }
+    }
+}
+
+/// Protocol for a signal, from OTEL_EXPORTER_OTLP_{SIGNAL}_PROTOCOL or OTEL_EXPORTER_OTLP_PROTOCOL
+/// (default grpc).
+pub fn get_protocol(signal: Signal) -> OtlpProtocol {
+    match get_signal_var(signal, "PROTOCOL", true).as_deref() {
+        None | Some("grpc") => OtlpProtocol::Grpc,
+        Some("http/protobuf") => OtlpProtocol::HttpProtobuf,
+        Some("http/json") => OtlpProtocol::HttpJson,
+        Some(other) => {
+            tracing::warn!("Unsupported OTLP protocol {}, using grpc", other);
+            OtlpProtocol::Grpc
+        }
+    }
+}
+
+pub struct OtlpConfig {
+    pub endpoint: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub timeout: Option<Duration>,
+    pub compression: Option<Compression>,
+    pub tls: TlsConfig,
+}
+
+/// PEM file paths from OTEL_EXPORTER_OTLP_CERTIFICATE, _CLIENT_KEY and _CLIENT_CERTIFICATE, and
+/// OTEL_EXPORTER_OTLP_INSECURE.
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    pub certificate: Option<String>,
+    pub client_key: Option<String>,
+    pub client_certificate: Option<String>,
+    pub insecure: bool,
+}
+
+/// Resolve the OTLP config for a signal. Signal-specific variables take precedence over the
+/// generic ones, and for http the signal path is appended to a generic endpoint. A gRPC endpoint
+/// without a scheme uses https, unless OTEL_EXPORTER_OTLP_INSECURE is true.
+pub fn get_otlp_config(signal: Signal, is_http: bool) -> OtlpConfig {
+    let tls = TlsConfig {
+        certificate: get_signal_var(signal, "CERTIFICATE", true),
+        client_key: get_signal_var(signal, "CLIENT_KEY", true),
+        client_certificate: get_signal_var(signal, "CLIENT_CERTIFICATE", true),
+        insecure: get_signal_var(signal, "INSECURE", true).is_some_and(|v| v.trim().eq_ignore_ascii_case("true")),
+    };
+    let endpoint = match get_signal_var(signal, "ENDPOINT", false) {
+        Some(endpoint) => Some(endpoint),
+        None => config::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .ok()
+            .filter(|e| !e.is_empty())
+            .map(|endpoint| {
+                if is_http {
+                    format!("{}{}", endpoint.trim_end_matches('/'), signal.http_path())
+                } else {
+                    endpoint
+                }
+            }),
+    };
+    let endpoint = endpoint.map(|endpoint| {
+        if is_http || endpoint.contains("://") {
+            endpoint
+        } else if tls.insecure {
+            format!("http://{}", endpoint)
+        } else {
+            format!("https://{}", endpoint)
+        }
+    });
+    let mut headers = config::env::var("OTEL_EXPORTER_OTLP_HEADERS")
+        .map(|h| parse_headers(&h))
+        .unwrap_or_default();
+    if let Some(signal_headers) = get_signal_var(signal, "HEADERS", false) {
+        headers.extend(parse_headers(&signal_headers));
+    }
+    let timeout = get_signal_var(signal, "TIMEOUT", true)
+        .and_then(|t| t.trim().parse::<u64>().ok())
+        .map(Duration::from_millis);
+    let compression = match get_signal_var(signal, "COMPRESSION", true).as_deref() {
+        None | Some("none") => None,
+        Some("gzip") => Some(Compression::Gzip),
+        Some("zstd") => Some(Compression::Zstd),
+        Some(other) => {
+            tracing::warn!("Unsupported OTLP compression {}, not compressing", other);
+            None
+        }
+    };
+    OtlpConfig {
+        endpoint,
+        headers,
+        timeout,
+        compression,
+        tls,
+    }
+}
+
+fn read_pem(path: &str) -> Option<Vec<u8>> {
+    match fs::read(path) {
+        Ok(pem) => Some(pem),
+        Err(err) => {
+            tracing::warn!("Failed to read OTLP certificate {}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Client certificate and key, if both are configured and readable.
+fn client_identity(tls: &TlsConfig) -> Option<(Vec<u8>, Vec<u8>)> {
+    match (&tls.client_certificate, &tls.client_key) {
+        (Some(certificate), Some(key)) => Some((read_pem(certificate)?, read_pem(key)?)),
+        (None, None) => None,
+        _ => {
+            tracing::warn!("OTLP client certificate and client key must both be set, ignoring");
+            None
+        }
+    }
+}
+
+/// TLS config for a gRPC channel to an https endpoint (none for http): native roots, plus any
+/// configured CA certificate and client identity.
+pub fn grpc_tls_config(endpoint: &str, tls: &TlsConfig) -> Option<ClientTlsConfig> {
+    if !endpoint.starts_with("https://") {
+        return None;
+    }
+    let mut tls_config = ClientTlsConfig::new().with_native_roots();
+    if let Some(certificate) = tls.certificate.as_deref().and_then(read_pem) {
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(certificate));
+    }
+    if let Some((certificate, key)) = client_identity(tls) {
+        tls_config = tls_config.identity(Identity::from_pem(certificate, key));
+    }
+    Some(tls_config)
+}
+
+/// Blocking http client for OTLP/http, with the configured CA certificate and client identity.
+/// With OTEL_EXPORTER_OTLP_INSECURE=true, server certificates are not verified. If the certificate
+/// or identity is not valid PEM, it is logged and ignored, rather than failing the export setup.
+pub fn http_client(timeout: Duration, tls: &TlsConfig) -> reqwest::Result<reqwest::blocking::Client> {
+    let builder = || {
+        let builder = reqwest::blocking::Client::builder().timeout(timeout);
+        if tls.insecure {
+            builder.danger_accept_invalid_certs(true)
+        } else {
+            builder
+        }
+    };
+    let mut configured = builder();
+    if let Some(certificate) = tls.certificate.as_deref().and_then(read_pem) {
+        match reqwest::Certificate::from_pem(&certificate) {
+            Ok(certificate) => configured = configured.add_root_certificate(certificate),
+            Err(err) => tracing::warn!("Ignoring invalid OTLP certificate: {}", err),
+        }
+    }
+    if let Some((mut certificate, key)) = client_identity(tls) {
+        certificate.push(b'\n');
+        certificate.extend_from_slice(&key);
+        match reqwest::Identity::from_pem(&certificate) {
+            Ok(identity) => configured = configured.identity(identity),
+            Err(err) => tracing::warn!("Ignoring invalid OTLP client certificate or key: {}", err),
+        }
+    }
+    // certificates are only parsed when the client is built
+    configured.build().or_else(|err| {
+        tracing::warn!("Failed to create OTLP http client with the configured certificates, ignoring them: {}", err);
+        builder().build()
+    })
+}
+
+/// Get OTEL_EXPORTER_OTLP_{SIGNAL}_{name}, optionally falling back to OTEL_EXPORTER_OTLP_{name}.
+fn get_signal_var(signal: Signal, name: &str, fallback: bool) -> Option<String> {
+    config::env::var(&format!("OTEL_EXPORTER_OTLP_{}_{}", signal.env_name(), name))
+        .ok()
+        .or_else(|| {
+            if fallback {
+                config::env::var(&format!("OTEL_EXPORTER_OTLP_{}", name)).ok()
+            } else {
+                None
+            }
+        })
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse a W3C baggage-style list of headers (key1=value1,key2=value2), with percent-encoded values.
+fn parse_headers(s: &str) -> HashMap<String, String> {
+    s.split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() {
+                return None;
+            }
+            Some((key.to_string(), percent_decode(value.trim())))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = (char::from(bytes[i + 1]).to_digit(16), char::from(bytes[i + 2]).to_digit(16));
+            if let (Some(high), Some(low)) = hex {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Convert headers to gRPC metadata, skipping (and logging) any which are not valid.
+pub fn to_metadata(headers: &HashMap<String, String>) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in headers {
+        match (key.to_lowercase().parse::<MetadataKey<Ascii>>(), value.parse::<MetadataValue<Ascii>>()) {
+            (Ok(key), Ok(value)) => {
+                metadata.insert(key, value);
+            }
+            _ => tracing::warn!("Ignoring invalid OTLP header {}", key),
+        }
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::env::with_request_vars;
+
+    #[test]
+    fn test_protocol() {
+        let protocol = |signal: Signal, vars: &[(&str, &str)]| with_request_vars(vars, || get_protocol(signal));
+        assert_eq!(protocol(Signal::Traces, &[]), OtlpProtocol::Grpc);
+        assert_eq!(protocol(Signal::Traces, &[("OTEL_EXPORTER_OTLP_PROTOCOL", "http/json")]), OtlpProtocol::HttpJson);
+        let vars = [
+            ("OTEL_EXPORTER_OTLP_PROTOCOL", "http/protobuf"),
+            ("OTEL_EXPORTER_OTLP_LOGS_PROTOCOL", "http/json"),
+        ];
+        assert_eq!(protocol(Signal::Traces, &vars), OtlpProtocol::HttpProtobuf);
+        assert_eq!(protocol(Signal::Logs, &vars), OtlpProtocol::HttpJson);
+        assert_eq!(protocol(Signal::Traces, &[("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL", "thrift")]), OtlpProtocol::Grpc);
+    }
+
+    #[test]
+    fn test_grpc_endpoint_scheme() {
+        let endpoint = |vars: &[(&str, &str)]| with_request_vars(vars, || get_otlp_config(Signal::Traces, false).endpoint);
+        assert_eq!(endpoint(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "collector:4317")]).as_deref(), Some("https://collector:4317"));
+        assert_eq!(
+            endpoint(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "collector:4317"), ("OTEL_EXPORTER_OTLP_INSECURE", "true")]).as_deref(),
+            Some("http://collector:4317")
+        );
+        assert_eq!(
+            endpoint(&[("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "http://traces:4317"), ("OTEL_EXPORTER_OTLP_ENDPOINT", "collector:4317")]).as_deref(),
+            Some("http://traces:4317")
+        );
+        assert_eq!(endpoint(&[]), None);
+    }
+
+    #[test]
+    fn test_http_endpoint_path() {
+        let endpoint = |signal: Signal, vars: &[(&str, &str)]| with_request_vars(vars, || get_otlp_config(signal, true).endpoint);
+        assert_eq!(
+            endpoint(Signal::Logs, &[("OTEL_EXPORTER_OTLP_ENDPOINT", "https://collector:4318/")]).as_deref(),
+            Some("https://collector:4318/v1/logs")
+        );
+        // signal-specific endpoints are used as-is, and http endpoints are not given a scheme
+        assert_eq!(
+            endpoint(Signal::Traces, &[("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "collector:4318/custom")]).as_deref(),
+            Some("collector:4318/custom")
+        );
+    }
+
+    #[test]
+    fn test_compression() {
+        let compression = |vars: &[(&str, &str)]| with_request_vars(vars, || get_otlp_config(Signal::Traces, true).compression);
+        assert_eq!(compression(&[("OTEL_EXPORTER_OTLP_COMPRESSION", "gzip")]), Some(Compression::Gzip));
+        assert_eq!(compression(&[("OTEL_EXPORTER_OTLP_COMPRESSION", "zstd")]), Some(Compression::Zstd));
+        assert_eq!(compression(&[("OTEL_EXPORTER_OTLP_COMPRESSION", "none")]), None);
+        assert_eq!(compression(&[("OTEL_EXPORTER_OTLP_COMPRESSION", "brotli")]), None);
+        assert_eq!(
+            compression(&[("OTEL_EXPORTER_OTLP_COMPRESSION", "gzip"), ("OTEL_EXPORTER_OTLP_TRACES_COMPRESSION", "zstd")]),
+            Some(Compression::Zstd)
+        );
+    }
+
+    #[test]
+    fn test_http_client_ignores_invalid_pem() {
+        let path = std::env::temp_dir().join(format!("otel-invalid-{}.pem", std::process::id()));
+        fs::write(&path, "-----BEGIN CERTIFICATE-----\nnot a certificate\n-----END CERTIFICATE-----\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let tls = TlsConfig {
+            certificate: Some(path.clone()),
+            client_key: Some(path.clone()),
+            client_certificate: Some(path.clone()),
+            insecure: false,
+        };
+        assert!(http_client(DEFAULT_TIMEOUT, &tls).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_headers() {
+        let headers = with_request_vars(
+            &[
+                ("OTEL_EXPORTER_OTLP_HEADERS", "api-key=a%20b,x-tenant=one"),
+                ("OTEL_EXPORTER_OTLP_TRACES_HEADERS", "x-tenant=two"),
+            ],
+            || get_otlp_config(Signal::Traces, true).headers,
+        );
+        assert_eq!(headers.get("api-key").map(String::as_str), Some("a b"));
+        assert_eq!(headers.get("x-tenant").map(String::as_str), Some("two"));
+    }
+}
@@ -28,14 +28,42 @@ const CONTEXT_STORAGE_CLASS_NAME: &str = r"OpenTelemetry\Context\Storage";
 pub type StorageClass = StateClass<()>;
 pub type StorageClassEntity = ClassEntity<()>;
 
+/// An attached context guard, along with the context it attached so that it can be detached and
+/// re-attached when a fiber is suspended and resumed. `seq` records the order of attachment.
+struct StoredGuard {
+    guard: ContextGuard,
+    context: Context,
+    seq: u64,
+}
+
+impl StoredGuard {
+    fn attach(context: Context) -> Self {
+        let guard = context.clone().attach();
+        Self { guard, context, seq: new_attach_seq() }
+    }
+}
+
+/// Where a guard was stored before its fiber was suspended
+enum GuardSlot {
+    Stack(u64),
+    Observer(usize),
+    FiberBase,
+}
+
 // When a Context is activated, it is stored in CONTEXT_STORAGE, and a reference to the
 // context created and stored as a class property.
+// GUARD_STACK, CONTEXT_GUARD_MAP and FIBER_BASE_GUARD only ever hold guards for the running fiber:
+// when a fiber is suspended, its guards are detached and moved to SUSPENDED_FIBERS.
 thread_local! {
     static CONTEXT_STORAGE: RefCell<HashMap<u64, Arc<Context>>> = RefCell::new(HashMap::new());
-    static GUARD_STACK: RefCell<Vec<(ContextGuard, u64)>> = RefCell::new(Vec::new());
-    static CONTEXT_GUARD_MAP: RefCell<HashMap<usize, ContextGuard>> = RefCell::new(HashMap::new()); //for observer use
+    static GUARD_STACK: RefCell<Vec<(StoredGuard, u64)>> = RefCell::new(Vec::new());
+    static CONTEXT_GUARD_MAP: RefCell<HashMap<usize, StoredGuard>> = RefCell::new(HashMap::new()); //for observer use
+    static FIBER_BASE_GUARD: RefCell<Option<StoredGuard>> = RefCell::new(None);
+    static SUSPENDED_GENERATORS: RefCell<HashMap<usize, Option<Context>>> = RefCell::new(HashMap::new());
+    static SUSPENDED_FIBERS: RefCell<HashMap<usize, Vec<(GuardSlot, Context, u64)>>> = RefCell::new(HashMap::new());
 }
 static INSTANCE_COUNTER: AtomicU64 = AtomicU64::new(1);
+static ATTACH_SEQ: AtomicU64 = AtomicU64::new(1);
 
 pub fn current_context() -> Arc<Context> {
     current_context_instance_id()
@@ -132,7 +160,7 @@ pub fn attach_context(instance_id: Option<u64>) -> Result<(), &'static str> {
                 id,
                 Arc::strong_count(&context)
             );
-            let guard = (*context_guard).clone().attach();
+            let guard = StoredGuard::attach((*context_guard).clone());
             GUARD_STACK.with(|stack| {
                 stack.borrow_mut().push((guard, id));
             });
@@ -167,6 +195,10 @@ fn new_instance_id() -> u64 {
     INSTANCE_COUNTER.fetch_add(1, Ordering::Relaxed)
 }
 
+fn new_attach_seq() -> u64 {
+    ATTACH_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
 pub fn new_storage_class() -> StorageClassEntity {
     ClassEntity::<()>::new_with_default_state_constructor(CONTEXT_STORAGE_CLASS_NAME)
 }
@@ -241,20 +273,129 @@ pub fn get_context_ids() -> Vec<u64> {
     })
 }
 
+/// Store a guard for an observed function. The guard must have just been attached, so that the
+/// current context is the one it attached.
 pub fn store_guard(exec_data: *mut ExecuteData, guard: ContextGuard) {
     let key = exec_data as *const ExecuteData as usize;
+    let stored = StoredGuard { guard, context: Context::current(), seq: new_attach_seq() };
     CONTEXT_GUARD_MAP.with(|map| {
-        map.borrow_mut().insert(key, guard);
+        map.borrow_mut().insert(key, stored);
     });
 }
 
 pub fn take_guard(exec_data: *mut ExecuteData) -> Option<ContextGuard> {
     let key = exec_data as *const ExecuteData as usize;
-    CONTEXT_GUARD_MAP.with(|map| map.borrow_mut().remove(&key))
+    CONTEXT_GUARD_MAP.with(|map| map.borrow_mut().remove(&key)).map(|stored| stored.guard)
+}
+
+/// A generator is yielding: detach its guard (if any), but keep its context so that the span stays
+/// open until the generator is resumed.
+pub fn suspend_guard(exec_data: *mut ExecuteData) {
+    let key = exec_data as *const ExecuteData as usize;
+    let context = CONTEXT_GUARD_MAP
+        .with(|map| map.borrow_mut().remove(&key))
+        .map(|stored| stored.context);
+    debug!("Suspending generator {:x} (has context = {})", key, context.is_some());
+    SUSPENDED_GENERATORS.with(|generators| {
+        generators.borrow_mut().insert(key, context);
+    });
+}
+
+/// A generator is being resumed: re-attach the context it was suspended with. Returns false if
+/// the generator was not suspended, ie this is its first execution.
+pub fn resume_guard(exec_data: *mut ExecuteData) -> bool {
+    let key = exec_data as *const ExecuteData as usize;
+    match SUSPENDED_GENERATORS.with(|generators| generators.borrow_mut().remove(&key)) {
+        Some(context) => {
+            debug!("Resuming generator {:x} (has context = {})", key, context.is_some());
+            if let Some(context) = context {
+                let stored = StoredGuard::attach(context);
+                CONTEXT_GUARD_MAP.with(|map| {
+                    map.borrow_mut().insert(key, stored);
+                });
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// A generator object is being destroyed, possibly while suspended (eg a loop over it broke early),
+/// in which case the observer never sees it complete: discard its suspended context, so that it is
+/// not attached to a new generator which reuses its execute_data.
+pub fn discard_generator(exec_data: *mut ExecuteData) {
+    let key = exec_data as *const ExecuteData as usize;
+    let discarded = SUSPENDED_GENERATORS.with(|generators| generators.borrow_mut().remove(&key));
+    if let Some(context) = discarded {
+        debug!("Discarding destroyed generator {:x} (has context = {})", key, context.is_some());
+    }
+}
+
+/// Swap context between fibers. All guards belonging to the fiber being switched away from are
+/// detached (newest first) and saved, then those of the fiber being switched to are re-attached.
+/// A fiber which has not run before inherits the context that was current when it was started.
+pub fn switch_fiber(from: usize, to: usize, from_finished: bool) {
+    debug!("Switching fiber {:x} -> {:x}", from, to);
+    let inherited = Context::current();
+    let saved = detach_fiber_guards();
+    if !from_finished {
+        SUSPENDED_FIBERS.with(|fibers| {
+            fibers.borrow_mut().insert(from, saved);
+        });
+    }
+    match SUSPENDED_FIBERS.with(|fibers| fibers.borrow_mut().remove(&to)) {
+        Some(saved) => reattach_fiber_guards(saved),
+        None => {
+            let stored = StoredGuard::attach(inherited);
+            FIBER_BASE_GUARD.with(|cell| *cell.borrow_mut() = Some(stored));
+        }
+    }
+}
+
+fn detach_fiber_guards() -> Vec<(GuardSlot, Context, u64)> {
+    let mut guards: Vec<(GuardSlot, StoredGuard)> = Vec::new();
+    GUARD_STACK.with(|stack| {
+        guards.extend(stack.borrow_mut().drain(..).map(|(stored, id)| (GuardSlot::Stack(id), stored)));
+    });
+    CONTEXT_GUARD_MAP.with(|map| {
+        guards.extend(map.borrow_mut().drain().map(|(key, stored)| (GuardSlot::Observer(key), stored)));
+    });
+    if let Some(stored) = FIBER_BASE_GUARD.with(|cell| cell.borrow_mut().take()) {
+        guards.push((GuardSlot::FiberBase, stored));
+    }
+    // guards must be dropped in reverse order of attachment
+    guards.sort_by(|a, b| b.1.seq.cmp(&a.1.seq));
+    let mut saved: Vec<(GuardSlot, Context, u64)> = guards
+        .into_iter()
+        .map(|(slot, stored)| {
+            let StoredGuard { guard, context, seq } = stored;
+            drop(guard);
+            (slot, context, seq)
+        })
+        .collect();
+    saved.reverse();
+    saved
+}
+
+fn reattach_fiber_guards(saved: Vec<(GuardSlot, Context, u64)>) {
+    for (slot, context, seq) in saved {
+        let guard = context.clone().attach();
+        let stored = StoredGuard { guard, context, seq };
+        match slot {
+            GuardSlot::Stack(id) => GUARD_STACK.with(|stack| stack.borrow_mut().push((stored, id))),
+            GuardSlot::Observer(key) => CONTEXT_GUARD_MAP.with(|map| {
+                map.borrow_mut().insert(key, stored);
+            }),
+            GuardSlot::FiberBase => FIBER_BASE_GUARD.with(|cell| *cell.borrow_mut() = Some(stored)),
+        }
+    }
 }
 
 pub fn clear_context_storage() {
     CONTEXT_STORAGE.with(|storage| storage.borrow_mut().clear());
     GUARD_STACK.with(|stack| stack.borrow_mut().clear());
     CONTEXT_GUARD_MAP.with(|map| map.borrow_mut().clear());
+    FIBER_BASE_GUARD.with(|cell| cell.borrow_mut().take());
+    SUSPENDED_GENERATORS.with(|generators| generators.borrow_mut().clear());
+    SUSPENDED_FIBERS.with(|fibers| fibers.borrow_mut().clear());
 }
\ No newline at end of file
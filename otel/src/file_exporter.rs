@@ -0,0 +1,242 @@
+// OTLP file exporter (OTEL_TRACES_EXPORTER / OTEL_LOGS_EXPORTER = otlp_file). Each batch is
+// appended as one line of OTLP-JSON (an Export*ServiceRequest) to {dir}/{signal}-{pid}.jsonl, so
+// that processes never interleave writes. Files are rotated by size, keeping a number of older
+// files ({name}.1 being the most recent). Providers writing to the same file share one
+// JsonLinesFile, so that its size is tracked (and the file rotated) in one place.
+use crate::{
+    config::{self, otlp::Signal},
+    otlp_encode::{logs_request, spans_request},
+};
+use opentelemetry_proto::{
+    tonic::collector::trace::v1::ExportTraceServiceRequest,
+    transform::common::tonic::ResourceAttributesWithSchema,
+};
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    logs::{LogBatch, LogExporter},
+    trace::{SpanData, SpanExporter},
+    Resource,
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    process,
+    sync::{Arc, Mutex},
+};
+
+const DEFAULT_DIR: &str = "/var/log/otel";
+
+/// Files in use, by signal and directory.
+static FILES: Lazy<Mutex<HashMap<(&'static str, PathBuf), Arc<JsonLinesFile>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug)]
+struct OpenFile {
+    pid: u32,
+    file: File,
+    size: u64,
+}
+
+/// A per-process JSON lines file, with size-based rotation.
+#[derive(Debug)]
+pub struct JsonLinesFile {
+    dir: PathBuf,
+    signal: Signal,
+    max_size: u64,
+    max_files: usize,
+    current: Mutex<Option<OpenFile>>,
+}
+
+impl JsonLinesFile {
+    /// From OTEL_EXPORTER_OTLP_FILE_PATH (directory), OTEL_EXPORTER_OTLP_FILE_MAX_SIZE (MB, default
+    /// 100) and OTEL_EXPORTER_OTLP_FILE_MAX_FILES (rotated files to keep, default 5).
+    pub fn from_config(signal: Signal) -> Arc<Self> {
+        let get = |name: &str, default: u64| {
+            config::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let dir = config::env::var("OTEL_EXPORTER_OTLP_FILE_PATH")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .unwrap_or_else(|| DEFAULT_DIR.to_string());
+        Self::shared(
+            PathBuf::from(dir),
+            signal,
+            get("OTEL_EXPORTER_OTLP_FILE_MAX_SIZE", 100) * 1024 * 1024,
+            get("OTEL_EXPORTER_OTLP_FILE_MAX_FILES", 5) as usize,
+        )
+    }
+
+    /// The process-wide file for a signal and directory, created with these limits if it is not
+    /// yet in use (otherwise the limits it was created with apply).
+    pub fn shared(dir: PathBuf, signal: Signal, max_size: u64, max_files: usize) -> Arc<Self> {
+        let mut files = match FILES.lock() {
+            Ok(files) => files,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        files
+            .entry((signal.name(), dir.clone()))
+            .or_insert_with(|| Arc::new(Self::new(dir, signal, max_size, max_files)))
+            .clone()
+    }
+
+    pub fn new(dir: PathBuf, signal: Signal, max_size: u64, max_files: usize) -> Self {
+        Self {
+            dir,
+            signal,
+            max_size,
+            max_files,
+            current: Mutex::new(None),
+        }
+    }
+
+    fn path(&self, pid: u32) -> PathBuf {
+        self.dir.join(format!("{}-{}.jsonl", self.signal.name(), pid))
+    }
+
+    fn open(&self, pid: u32) -> io::Result<OpenFile> {
+        fs::create_dir_all(&self.dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(self.path(pid))?;
+        let size = file.metadata()?.len();
+        Ok(OpenFile { pid, file, size })
+    }
+
+    /// Rename {name} to {name}.1, {name}.1 to {name}.2 etc, dropping the oldest.
+    fn rotate(&self, pid: u32) -> io::Result<()> {
+        let path = self.path(pid);
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+        if self.max_files == 0 {
+            return fs::remove_file(&path);
+        }
+        let _ = fs::remove_file(rotated(self.max_files));
+        for n in (1..self.max_files).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        fs::rename(&path, rotated(1))
+    }
+
+    pub fn append(&self, line: &str) -> io::Result<()> {
+        let pid = process::id();
+        let mut current = match self.current.lock() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        // a forked child writes to its own file
+        if current.as_ref().is_none_or(|open| open.pid != pid) {
+            *current = Some(self.open(pid)?);
+        }
+        let needs_rotation = current.as_ref().is_some_and(|open| {
+            open.size > 0 && open.size + line.len() as u64 + 1 > self.max_size
+        });
+        if needs_rotation {
+            *current = None;
+            self.rotate(pid)?;
+            *current = Some(self.open(pid)?);
+        }
+        let open = current.as_mut().expect("file was opened");
+        let mut buffer = Vec::with_capacity(line.len() + 1);
+        buffer.extend_from_slice(line.as_bytes());
+        buffer.push(b'\n');
+        open.file.write_all(&buffer)?;
+        open.size += buffer.len() as u64;
+        Ok(())
+    }
+}
+
+fn write_line(file: &JsonLinesFile, line: serde_json::Result<String>) -> OTelSdkResult {
+    let line = line
+        .map_err(|err| OTelSdkError::InternalFailure(format!("failed to encode OTLP-JSON: {}", err)))?;
+    file.append(&line)
+        .map_err(|err| OTelSdkError::InternalFailure(format!("failed to write to {:?}: {}", file.dir, err)))
+}
+
+#[derive(Debug)]
+pub struct FileSpanExporter {
+    file: Arc<JsonLinesFile>,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl FileSpanExporter {
+    pub fn new(file: Arc<JsonLinesFile>) -> Self {
+        Self {
+            file,
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+
+    /// Write a request which is already built (eg with the spans of several resources).
+    pub fn export_request(&self, request: &ExportTraceServiceRequest) -> OTelSdkResult {
+        write_line(&self.file, serde_json::to_string(request))
+    }
+}
+
+impl SpanExporter for FileSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        self.export_request(&spans_request(batch, &self.resource))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+    }
+}
+
+#[derive(Debug)]
+pub struct FileLogExporter {
+    file: Arc<JsonLinesFile>,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl FileLogExporter {
+    pub fn new(file: Arc<JsonLinesFile>) -> Self {
+        Self {
+            file,
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+}
+
+impl LogExporter for FileLogExporter {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        write_line(&self.file, serde_json::to_string(&logs_request(batch, &self.resource)))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("otel-file-exporter-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let file = JsonLinesFile::new(dir.clone(), Signal::Traces, 10, 2);
+        for line in ["aaaa", "bbbb", "cccc", "dddd", "eeee"] {
+            file.append(line).unwrap();
+        }
+        let path = file.path(process::id());
+        let read = |suffix: &str| fs::read_to_string(format!("{}{}", path.display(), suffix)).unwrap();
+        assert_eq!(read(""), "eeee\n");
+        assert_eq!(read(".1"), "cccc\ndddd\n");
+        assert_eq!(read(".2"), "aaaa\nbbbb\n");
+        assert!(fs::metadata(format!("{}.3", path.display())).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_shared_per_signal_and_dir() {
+        let dir = std::env::temp_dir().join(format!("otel-file-shared-{}", process::id()));
+        let file = JsonLinesFile::shared(dir.clone(), Signal::Logs, 10, 2);
+        assert!(Arc::ptr_eq(&file, &JsonLinesFile::shared(dir.clone(), Signal::Logs, 20, 3)));
+        assert_eq!(file.max_size, 10);
+        assert!(!Arc::ptr_eq(&file, &JsonLinesFile::shared(dir.clone(), Signal::Traces, 10, 2)));
+        assert!(!Arc::ptr_eq(&file, &JsonLinesFile::shared(dir.join("other"), Signal::Logs, 10, 2)));
+    }
+}
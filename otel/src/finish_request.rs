@@ -0,0 +1,55 @@
+// fastcgi_finish_request (php-fpm, and FrankenPHP's alias) and litespeed_finish_request send the
+// response to the client while the script carries on. Their internal handlers are wrapped so that
+// the root span ends when the response has been sent, and the remaining work is traced by a
+// post-response span. Spans and logs buffered by then are exported at that point, after the
+// response rather than before it.
+use crate::{
+    internal_function::{self, Upstream},
+    request,
+};
+use phper::{
+    sys,
+    values::ZVal,
+};
+#[cfg(feature = "test")]
+use {
+    crate::util::get_sapi_module_name,
+    phper::modules::Module,
+    std::convert::Infallible,
+};
+
+static UPSTREAM_FASTCGI_FINISH_REQUEST: Upstream = Upstream::new();
+static UPSTREAM_LITESPEED_FINISH_REQUEST: Upstream = Upstream::new();
+
+/// Wrap the finish_request functions of the current SAPI, if it has any.
+pub fn init_once() {
+    internal_function::wrap("fastcgi_finish_request", fastcgi_finish_request, &UPSTREAM_FASTCGI_FINISH_REQUEST);
+    internal_function::wrap("litespeed_finish_request", litespeed_finish_request, &UPSTREAM_LITESPEED_FINISH_REQUEST);
+}
+
+/// For tests, the CLI (which has no finish_request function) gets a fastcgi_finish_request which
+/// sends nothing and returns true, to be wrapped like php-fpm's.
+#[cfg(feature = "test")]
+pub fn add_test_function(module: &mut Module) {
+    if get_sapi_module_name() == "cli" {
+        module.add_function("fastcgi_finish_request", |_| Ok::<_, Infallible>(true));
+    }
+}
+
+unsafe extern "C" fn fastcgi_finish_request(execute_data: *mut sys::zend_execute_data, return_value: *mut sys::zval) {
+    unsafe { finish_request(&UPSTREAM_FASTCGI_FINISH_REQUEST, execute_data, return_value) }
+}
+
+unsafe extern "C" fn litespeed_finish_request(execute_data: *mut sys::zend_execute_data, return_value: *mut sys::zval) {
+    unsafe { finish_request(&UPSTREAM_LITESPEED_FINISH_REQUEST, execute_data, return_value) }
+}
+
+unsafe fn finish_request(upstream: &Upstream, execute_data: *mut sys::zend_execute_data, return_value: *mut sys::zval) {
+    unsafe {
+        upstream.call(execute_data, return_value);
+        // the root span is only ended once the response has actually been sent
+        if ZVal::from_mut_ptr(return_value).as_bool() == Some(true) {
+            request::on_finish_request();
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// Fork handling. pcntl_fork's internal handler is wrapped so that exports are flushed in the parent
+// before forking, and the child starts with its own providers, tokio runtime and root span. The
+// child must never shut down (or drop) what it inherited: batch export threads only exist in the
+// parent, and anything still buffered would be exported twice.
+use crate::{
+    internal_function::{self, Upstream},
+    logs::logger_provider,
+    request,
+    runtime,
+    trace::{shared_pipeline, tracer_provider},
+};
+use phper::{
+    sys,
+    values::ZVal,
+};
+
+static UPSTREAM_PCNTL_FORK: Upstream = Upstream::new();
+
+/// Wrap pcntl_fork, if the pcntl extension is loaded.
+pub fn init_once() {
+    if !internal_function::wrap("pcntl_fork", pcntl_fork, &UPSTREAM_PCNTL_FORK) {
+        tracing::debug!("pcntl_fork not found, fork handling disabled");
+    }
+}
+
+unsafe extern "C" fn pcntl_fork(execute_data: *mut sys::zend_execute_data, return_value: *mut sys::zval) {
+    before_fork();
+    unsafe {
+        UPSTREAM_PCNTL_FORK.call(execute_data, return_value);
+        let pid = ZVal::from_mut_ptr(return_value).as_long().unwrap_or(-1);
+        if pid == 0 {
+            after_fork_child();
+        }
+    }
+}
+
+/// Flush the parent's buffered spans and logs (of every cached provider, not only the current
+/// app's), so that they are not also inherited by the child.
+fn before_fork() {
+    tracing::debug!("fork: flushing providers in parent");
+    tracer_provider::force_flush_all();
+    logger_provider::force_flush_all();
+}
+
+fn after_fork_child() {
+    tracing::debug!("fork: resetting providers in child");
+    tracer_provider::forget_inherited();
+    shared_pipeline::forget_inherited();
+    logger_provider::forget_inherited();
+    runtime::forget_after_fork();
+    tracer_provider::init_once();
+    logger_provider::init_once();
+    request::on_fork_child();
+}
@@ -0,0 +1,66 @@
+// Wrapping of internal functions' handlers (eg pcntl_fork, fastcgi_finish_request), for behaviour
+// which must run around the function itself rather than in an observer. The function table is only
+// complete after all extensions and the SAPI's own functions have been registered, so handlers are
+// wrapped from RINIT. With ZTS, each thread has its own copy of the internal functions, so they are
+// wrapped once per thread.
+use phper::{eg, sys};
+use std::{cell::RefCell, collections::HashSet, sync::OnceLock};
+
+pub type InternalHandler = unsafe extern "C" fn(execute_data: *mut sys::zend_execute_data, return_value: *mut sys::zval);
+
+thread_local! {
+    /// Functions wrapped in this thread's function table.
+    static WRAPPED: RefCell<HashSet<&'static str>> = RefCell::new(HashSet::new());
+}
+
+/// The original handler of a wrapped function, which is the same for every thread's copy.
+pub struct Upstream(OnceLock<InternalHandler>);
+
+impl Upstream {
+    pub const fn new() -> Self {
+        Self(OnceLock::new())
+    }
+
+    /// Call the original handler.
+    ///
+    /// # Safety
+    /// The arguments must be those the wrapping handler was called with.
+    pub unsafe fn call(&self, execute_data: *mut sys::zend_execute_data, return_value: *mut sys::zval) {
+        if let Some(upstream) = self.0.get() {
+            unsafe { upstream(execute_data, return_value) };
+        }
+    }
+}
+
+impl Default for Upstream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Replace the handler of internal function `name` in the current thread's function table, keeping
+/// the original in `upstream`. Returns false if there is no such internal function.
+pub fn wrap(name: &'static str, handler: InternalHandler, upstream: &'static Upstream) -> bool {
+    if WRAPPED.with(|wrapped| wrapped.borrow().contains(name)) {
+        return true;
+    }
+    unsafe {
+        let zv = sys::zend_hash_str_find(eg!(function_table), name.as_ptr().cast(), name.len());
+        if zv.is_null() {
+            return false;
+        }
+        let function = (*zv).value.func;
+        if (*function).type_ as u32 != sys::ZEND_INTERNAL_FUNCTION {
+            return false;
+        }
+        // a thread's table may have been copied from one which was already wrapped, so only the
+        // first handler seen is the original
+        if let Some(original) = (*function).internal_function.handler {
+            upstream.0.get_or_init(|| original);
+        }
+        (*function).internal_function.handler = Some(handler);
+    }
+    WRAPPED.with(|wrapped| wrapped.borrow_mut().insert(name));
+    tracing::debug!("{} wrapped", name);
+    true
+}
@@ -4,18 +4,28 @@ use phper::{
 };
 use std::env;
 
+pub mod agent;
 pub mod context;
 pub mod trace;
 pub mod class_registry;
 pub mod config;
 pub mod error;
+pub mod file_exporter;
+pub mod finish_request;
+pub mod fork;
 pub mod globals;
+pub mod internal_function;
 pub mod request;
 pub mod logging;
 pub mod logs;
+pub mod provider_cache;
 pub mod runtime;
+pub mod sapi;
+pub mod spool;
 pub mod util;
 pub mod module;
+pub mod output;
+pub mod otlp_encode;
 pub mod auto;
 
 include!(concat!(env!("OUT_DIR"), "/package_versions.rs"));
@@ -32,6 +42,8 @@ pub fn get_module() -> Module {
     module::add_module_ini(&mut module);
 
     class_registry::register_classes_and_interfaces(&mut module);
+    #[cfg(feature = "test")]
+    finish_request::add_test_function(&mut module);
 
     module.on_module_init(module::on_module_init);
     module.on_module_shutdown(module::on_module_shutdown);
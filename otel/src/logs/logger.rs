@@ -6,17 +6,50 @@ use phper::{
 use std::{
     collections::HashSet,
     convert::Infallible,
+    process,
     sync::Mutex,
 };
 use once_cell::sync::Lazy;
-use opentelemetry::logs::{
-    Logger,
-    LogRecord,
+use opentelemetry::{
+    logs::{
+        Logger,
+        LoggerProvider,
+        LogRecord,
+    },
+    InstrumentationScope,
 };
 use opentelemetry_sdk::logs::SdkLogger;
-use crate::logs::log_record::{LOG_RECORD_CLASS_NAME, LogRecordState};
+use crate::logs::{
+    log_record::{LOG_RECORD_CLASS_NAME, LogRecordState},
+    logger_provider,
+};
+
+pub type LoggerClass = StateClass<Option<LoggerState>>;
 
-pub type LoggerClass = StateClass<Option<SdkLogger>>;
+/// A logger, with the scope and process it was created for.
+pub struct LoggerState {
+    logger: SdkLogger,
+    scope: InstrumentationScope,
+    pid: u32,
+}
+
+impl LoggerState {
+    pub fn new(logger: SdkLogger, scope: InstrumentationScope) -> Self {
+        Self { logger, scope, pid: process::id() }
+    }
+
+    /// The logger, rebound to the current provider if it was created before pcntl_fork (see
+    /// TracerState).
+    fn logger(&mut self) -> &SdkLogger {
+        let pid = process::id();
+        if self.pid != pid {
+            tracing::debug!("fork: rebinding logger {:?} to the child's logger provider", self.scope.name());
+            self.logger = logger_provider::get_logger_provider().logger_with_scope(self.scope.clone());
+            self.pid = pid;
+        }
+        &self.logger
+    }
+}
 
 const LOGGER_CLASS_NAME: &str = r"OpenTelemetry\API\Logs\Logger";
 static EVENT_NAMES: Lazy<Mutex<HashSet<&'static str>>> = Lazy::new(|| Mutex::new(HashSet::new()));
@@ -71,9 +104,9 @@ fn map_severity_text(text: &str) -> Option<&'static str> {
 
 pub fn make_logger_class(
     logger_interface: Interface,
-) -> ClassEntity<Option<SdkLogger>> {
+) -> ClassEntity<Option<LoggerState>> {
     let mut class =
-        ClassEntity::<Option<SdkLogger>>::new_with_default_state_constructor(LOGGER_CLASS_NAME);
+        ClassEntity::<Option<LoggerState>>::new_with_default_state_constructor(LOGGER_CLASS_NAME);
 
     class.implements(logger_interface);
     class.add_method("__construct", Visibility::Private, |_, _| {
@@ -83,7 +116,7 @@ pub fn make_logger_class(
     class
         .add_method("emit", Visibility::Public, |this, arguments| {
             tracing::debug!("Logger::emit called");
-            let logger: &SdkLogger = this.as_state().as_ref().unwrap();
+            let logger: &SdkLogger = this.as_mut_state().as_mut().unwrap().logger();
 
             let record_zval = &arguments[0];
             let record_obj = record_zval.expect_z_obj();
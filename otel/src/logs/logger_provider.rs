@@ -4,15 +4,15 @@ use phper::{
     types::{ArgumentTypeHint, ReturnTypeHint},
 };
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::HashSet,
     convert::Infallible,
-    env,
     process,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 use opentelemetry::{
     logs::LoggerProvider,
-    KeyValue,
     InstrumentationScope,
 };
 use opentelemetry_stdout::LogExporter as StdoutLogExporter;
@@ -21,68 +21,79 @@ use opentelemetry_sdk::{
         SimpleLogProcessor,
         BatchConfigBuilder,
         BatchLogProcessor,
+        LogExporter,
+        LoggerProviderBuilder,
         SdkLoggerProvider,
     },
     Resource,
 };
 use once_cell::sync::Lazy;
 use crate::{
+    agent::{self, AgentLogExporter},
+    config::{
+        self,
+        otlp::{self, OtlpProtocol, Signal},
+    },
+    file_exporter::{FileLogExporter, JsonLinesFile},
     logs::{
-        logger::LoggerClass,
+        logger::{LoggerClass, LoggerState},
         memory_exporter::MEMORY_EXPORTER,
     },
+    provider_cache::{self, CacheLimits, ProviderCache, ProviderKey},
     request,
+    spool::{RawSender, Spool, SpoolingLogExporter},
     util,
     runtime::init_tokio_runtime,
 };
 use opentelemetry_otlp::{
-    Protocol,
+    ExporterBuildError,
     LogExporter as OtlpLogExporter,
     WithExportConfig,
+    WithHttpConfig,
+    WithTonicConfig,
 };
 
 pub const LOGGER_PROVIDER_CLASS_NAME: &str = r"OpenTelemetry\API\Logs\LoggerProvider";
 
 pub type LoggerProviderClass = StateClass<()>;
 
-static LOGGER_PROVIDERS: Lazy<Mutex<HashMap<(u32, String), Arc<SdkLoggerProvider>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static LOGGER_PROVIDERS: Lazy<Mutex<ProviderCache<SdkLoggerProvider>>> = Lazy::new(|| Mutex::new(ProviderCache::new()));
 static NOOP_LOGGER_PROVIDER: Lazy<Arc<SdkLoggerProvider>> = Lazy::new(|| {
     Arc::new(SdkLoggerProvider::builder()
         .with_resource(Resource::builder_empty().build())
         .build())
 });
 
+thread_local! {
+    // providers leased by this thread's request (or FrankenPHP worker script), released at RSHUTDOWN
+    static LEASED_PROVIDERS: RefCell<HashSet<ProviderKey>> = RefCell::new(HashSet::new());
+}
+
 fn get_logger_provider_key() -> (u32, String) {
-    let pid = process::id();
-    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_default();
-    let resource_attrs = env::var("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default();
-    let key = format!("{}:{}", service_name, resource_attrs);
-    (pid, key)
+    (process::id(), config::env::provider_key())
 }
 
 pub fn init_once() {
     let key = get_logger_provider_key();
+    let limits = CacheLimits::from_ini();
     let mut providers = LOGGER_PROVIDERS.lock().unwrap();
-    if providers.contains_key(&key) {
+    let exists = providers.get(&key).is_some();
+    let mut evicted = providers.evict_idle(&limits, Instant::now());
+    if exists {
         tracing::debug!("logger provider already exists for key {:?}", key);
+        lease(&mut providers, &key);
+        drop(providers);
+        provider_cache::shutdown_evicted("logger", evicted);
         return;
     }
     tracing::debug!("creating logger provider for key {:?}", key);
 
-    let resource = Resource::builder()
-        .with_attribute(KeyValue::new("telemetry.sdk.language", "php"))
-        .with_attribute(KeyValue::new("telemetry.sdk.name", "ext-otel"))
-        .with_attribute(KeyValue::new("telemetry.sdk.version", env!("CARGO_PKG_VERSION")))
-        .with_attribute(KeyValue::new("process.runtime.name", util::get_sapi_module_name()))
-        .with_attribute(KeyValue::new("process.runtime.version", util::get_php_version()))
-        .with_attribute(KeyValue::new("process.pid", process::id().to_string()))
-        .with_attribute(KeyValue::new("host.name", hostname::get().unwrap_or_default().to_string_lossy().to_string()))
-        .build();
+    let resource = util::build_resource();
 
     let mut builder = SdkLoggerProvider::builder().with_resource(resource);
 
-    let exporter_type = env::var("OTEL_LOGS_EXPORTER").unwrap_or_else(|_| "otlp".to_string());
-    let use_simple = env::var("OTEL_LOGS_PROCESSOR").as_deref() == Ok("simple");
+    let exporter_type = config::env::var("OTEL_LOGS_EXPORTER").unwrap_or_else(|_| "otlp".to_string());
+    let use_simple = config::env::var("OTEL_LOGS_PROCESSOR").as_deref() == Ok("simple");
 
     if exporter_type == "none" {
         tracing::debug!("Using no-op log exporter");
@@ -113,47 +124,128 @@ pub fn init_once() {
                 .build();
             builder = builder.with_log_processor(batch);
         }
+    } else if exporter_type == "otlp_file" {
+        tracing::debug!("Using OTLP file log exporter");
+        builder = with_log_exporter(builder, FileLogExporter::new(JsonLinesFile::from_config(Signal::Logs)), use_simple);
+    } else if let Some(path) = agent::socket_path() {
+        let exporter = AgentLogExporter::new(path);
+        if use_simple {
+            tracing::debug!("Using Simple log processor with agent exporter");
+            builder = builder.with_log_processor(SimpleLogProcessor::new(exporter));
+        } else {
+            tracing::debug!("Using Batch log processor with agent exporter");
+            let batch_config = BatchConfigBuilder::default().build();
+            let batch = BatchLogProcessor::builder(exporter)
+                .with_batch_config(batch_config)
+                .build();
+            builder = builder.with_log_processor(batch);
+        }
     } else {
         // Default to OTLP exporter
-        if env::var("OTEL_EXPORTER_OTLP_PROTOCOL").as_deref() == Ok("http/protobuf") {
-            tracing::debug!("Using http/protobuf log exporter");
-            let exporter = OtlpLogExporter::builder()
-                .with_http()
-                .with_protocol(Protocol::HttpBinary)
-                .build()
-                .expect("Failed to create OTLP http log exporter");
-            if use_simple {
-                builder = builder.with_log_processor(SimpleLogProcessor::new(exporter));
-            } else {
-                let batch_config = BatchConfigBuilder::default().build();
-                let batch = BatchLogProcessor::builder(exporter)
-                    .with_batch_config(batch_config)
-                    .build();
-                builder = builder.with_log_processor(batch);
-            }
-        } else {
-            tracing::debug!("Using gRPC log exporter with tokio runtime");
-            let runtime = init_tokio_runtime();
-            let exporter = runtime.block_on(async {
-                OtlpLogExporter::builder()
-                    .with_tonic()
-                    .build()
-                    .expect("Failed to create OTLP grpc log exporter")
-            });
-            if use_simple {
-                builder = builder.with_log_processor(SimpleLogProcessor::new(exporter));
-            } else {
-                let batch_config = BatchConfigBuilder::default().build();
-                let batch = BatchLogProcessor::builder(exporter)
-                    .with_batch_config(batch_config)
-                    .build();
-                builder = builder.with_log_processor(batch);
-            }
+        let protocol = otlp::get_protocol(Signal::Logs);
+        match build_otlp_exporter(protocol) {
+            Ok(exporter) => match Spool::from_config() {
+                Some(spool) => {
+                    tracing::debug!("Using retry spool for log exporter");
+                    let exporter = SpoolingLogExporter::new(exporter, spool, RawSender::new(Signal::Logs, protocol));
+                    builder = with_log_exporter(builder, exporter, use_simple);
+                }
+                None => builder = with_log_exporter(builder, exporter, use_simple),
+            },
+            // eg an invalid endpoint in one app's .env: don't fail the request, just don't export its logs
+            Err(err) => tracing::warn!("Failed to create OTLP log exporter, logs will not be exported: {}", err),
         }
     }
 
     let provider = Arc::new(builder.build());
-    providers.insert(key, provider.clone());
+    evicted.extend(providers.insert(key.clone(), provider.clone(), &limits));
+    lease(&mut providers, &key);
+    tracing::debug!("{} logger providers live", providers.len());
+    drop(providers);
+    provider_cache::shutdown_evicted("logger", evicted);
+}
+
+/// OTLP log exporter for the protocol (http or gRPC). Fails if the configuration is invalid,
+/// eg an endpoint which is not a URI.
+fn build_otlp_exporter(protocol: OtlpProtocol) -> Result<OtlpLogExporter, ExporterBuildError> {
+    if protocol.is_http() {
+        tracing::debug!("Using {:?} log exporter", protocol);
+        let otlp_config = otlp::get_otlp_config(Signal::Logs, true);
+        let mut exporter_builder = OtlpLogExporter::builder()
+            .with_http()
+            .with_protocol(protocol.http_protocol())
+            .with_headers(otlp_config.headers);
+        if let Some(endpoint) = otlp_config.endpoint {
+            exporter_builder = exporter_builder.with_endpoint(endpoint);
+        }
+        let timeout = otlp_config.timeout.unwrap_or(otlp::DEFAULT_TIMEOUT);
+        exporter_builder = exporter_builder.with_timeout(timeout);
+        match otlp::http_client(timeout, &otlp_config.tls) {
+            Ok(client) => exporter_builder = exporter_builder.with_http_client(client),
+            Err(err) => tracing::warn!("Failed to create OTLP http client, using the exporter's default: {}", err),
+        }
+        if let Some(compression) = otlp_config.compression {
+            exporter_builder = exporter_builder.with_compression(compression);
+        }
+        exporter_builder.build()
+    } else {
+        tracing::debug!("Using gRPC log exporter with tokio runtime");
+        let runtime = init_tokio_runtime();
+        let otlp_config = otlp::get_otlp_config(Signal::Logs, false);
+        runtime.block_on(async {
+            let mut exporter_builder = OtlpLogExporter::builder()
+                .with_tonic()
+                .with_metadata(otlp::to_metadata(&otlp_config.headers));
+            if let Some(endpoint) = otlp_config.endpoint {
+                if let Some(tls_config) = otlp::grpc_tls_config(&endpoint, &otlp_config.tls) {
+                    exporter_builder = exporter_builder.with_tls_config(tls_config);
+                }
+                exporter_builder = exporter_builder.with_endpoint(endpoint);
+            }
+            if let Some(timeout) = otlp_config.timeout {
+                exporter_builder = exporter_builder.with_timeout(timeout);
+            }
+            if let Some(compression) = otlp_config.compression {
+                exporter_builder = exporter_builder.with_compression(compression);
+            }
+            exporter_builder.build()
+        })
+    }
+}
+
+/// Add an exporter, with a simple or batch processor
+fn with_log_exporter<E: LogExporter + 'static>(builder: LoggerProviderBuilder, exporter: E, use_simple: bool) -> LoggerProviderBuilder {
+    if use_simple {
+        builder.with_log_processor(SimpleLogProcessor::new(exporter))
+    } else {
+        let batch_config = BatchConfigBuilder::default().build();
+        let batch = BatchLogProcessor::builder(exporter)
+            .with_batch_config(batch_config)
+            .build();
+        builder.with_log_processor(batch)
+    }
+}
+
+/// Lease the provider until RSHUTDOWN, so that it is not evicted (and shut down) by another thread
+/// while this request's loggers may still use it.
+fn lease(providers: &mut ProviderCache<SdkLoggerProvider>, key: &ProviderKey) {
+    LEASED_PROVIDERS.with(|leased| {
+        if leased.borrow_mut().insert(key.clone()) {
+            providers.acquire(key);
+        }
+    });
+}
+
+/// Release the providers leased by this thread's request.
+pub fn release_leases() {
+    let leased = LEASED_PROVIDERS.with(|leased| std::mem::take(&mut *leased.borrow_mut()));
+    if leased.is_empty() {
+        return;
+    }
+    let mut providers = LOGGER_PROVIDERS.lock().unwrap();
+    for key in leased {
+        providers.release(&key);
+    }
 }
 
 pub fn get_logger_provider() -> Arc<SdkLoggerProvider> {
@@ -161,29 +253,68 @@ pub fn get_logger_provider() -> Arc<SdkLoggerProvider> {
         tracing::debug!("OpenTelemetry is disabled for this request, returning no-op logger provider");
         return NOOP_LOGGER_PROVIDER.clone();
     }
-    let providers = LOGGER_PROVIDERS.lock().unwrap();
+    let mut providers = LOGGER_PROVIDERS.lock().unwrap();
     let key = get_logger_provider_key();
     if let Some(provider) = providers.get(&key) {
-        return provider.clone();
+        return provider;
     } else {
         tracing::warn!("no logger provider initialized for key {:?}, using no-op", key);
         NOOP_LOGGER_PROVIDER.clone()
     }
 }
 
+pub fn force_flush() {
+    let pid = process::id();
+    let providers = LOGGER_PROVIDERS.lock().unwrap();
+    let key = get_logger_provider_key();
+    if let Some(provider) = providers.peek(&key) {
+        tracing::info!("Flushing LoggerProvider for pid {}", pid);
+        match provider.force_flush() {
+            Ok(_) => tracing::debug!("OpenTelemetry logger provider flush success"),
+            Err(err) => tracing::warn!("Failed to flush OpenTelemetry logger provider: {:?}", err),
+        }
+    } else {
+        tracing::info!("no logger provider to flush for pid {}", pid);
+    }
+}
+
+/// Flush every provider of this process (not only the current request's), eg before forking.
+pub fn force_flush_all() {
+    let pid = process::id();
+    let providers = LOGGER_PROVIDERS.lock().unwrap().providers_where(|(k_pid, _)| *k_pid == pid);
+    tracing::info!("Flushing {} logger providers for pid {}", providers.len(), pid);
+    for provider in providers {
+        if let Err(err) = provider.force_flush() {
+            tracing::warn!("Failed to flush OpenTelemetry logger provider: {:?}", err);
+        }
+    }
+}
+
+/// In a forked child, discard providers inherited from the parent without shutting them down (their
+/// export threads only exist in the parent).
+pub fn forget_inherited() {
+    let pid = process::id();
+    let mut providers = LOGGER_PROVIDERS.lock().unwrap();
+    for (key, provider) in providers.remove_where(|(k_pid, _)| *k_pid != pid) {
+        tracing::debug!("Forgetting inherited LoggerProvider for key {:?}", key);
+        std::mem::forget(provider);
+    }
+    LEASED_PROVIDERS.with(|leased| leased.borrow_mut().clear());
+}
+
+/// Number of cached logger providers, for phpinfo()
+pub fn provider_count() -> usize {
+    LOGGER_PROVIDERS.lock().map(|providers| providers.len()).unwrap_or(0)
+}
+
 pub fn shutdown() {
     let pid = process::id();
     let mut providers = LOGGER_PROVIDERS.lock().unwrap();
-    let keys_to_remove: Vec<_> = providers
-        .keys()
-        .filter(|(k_pid, _)| *k_pid == pid)
-        .cloned()
-        .collect();
-    if !keys_to_remove.is_empty() {
+    let removed = providers.remove_where(|(k_pid, _)| *k_pid == pid);
+    if !removed.is_empty() {
         tracing::info!("Shutting down all LoggerProviders for pid {}", pid);
-        for key in keys_to_remove {
+        for (key, _provider) in removed {
             tracing::debug!("Shutting down LoggerProvider for key {:?}", key);
-            providers.remove(&key);
         }
     } else {
         tracing::info!("no logger providers to shutdown for pid {}", pid);
@@ -233,9 +364,9 @@ pub fn make_logger_provider_class(
             }
             let scope = scope_builder.build();
 
-            let logger = provider.logger_with_scope(scope);
+            let logger = provider.logger_with_scope(scope.clone());
             let mut object = logger_class.init_object()?;
-            *object.as_mut_state() = Some(logger);
+            *object.as_mut_state() = Some(LoggerState::new(logger, scope));
 
             Ok::<_, phper::Error>(object)
         })
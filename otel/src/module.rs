@@ -2,16 +2,24 @@ use crate::{
     config,
     logging,
     logs::logger_provider,
+    sapi,
     util::get_sapi_module_name,
     auto,
-    trace::tracer_provider,
+    trace::{shared_pipeline, tracer_provider},
+};
+use phper::{
+    ini::ini_get,
+    modules::Module,
+    sys,
 };
-use phper::ini::ini_get;
 use once_cell::sync::OnceCell;
+use std::ffi::CString;
 use tracing;
-use phper::modules::Module;
 
 static DISABLED: OnceCell<bool> = OnceCell::new();
+static mut UPSTREAM_MODULE_INFO: Option<
+    unsafe extern "C" fn(zend_module: *mut sys::zend_module_entry),
+> = None;
 
 pub fn on_module_init() {
     logging::init_once();
@@ -24,6 +32,10 @@ pub fn on_module_init() {
         return;
     }
     tracing::debug!("OpenTelemetry::MINIT");
+    init_module_info();
+    if sapi != "cli" {
+        sapi::init();
+    }
 
     let auto_enabled = ini_get::<bool>(config::ini::OTEL_AUTO_ENABLED);
     if auto_enabled {
@@ -49,6 +61,7 @@ pub fn on_module_shutdown() {
     }
     tracing::debug!("OpenTelemetry::MSHUTDOWN");
     tracer_provider::shutdown();
+    shared_pipeline::shutdown();
     logger_provider::shutdown();
 }
 
@@ -70,6 +83,72 @@ pub fn add_module_info(module: &mut Module) {
     {
         module.add_info("auto-instrumentation", "zend_execute_ex".to_string());
     }
+    #[cfg(otel_zts)]
+    {
+        module.add_info("thread safety", "enabled (request-scoped environment)".to_string());
+    }
+}
+
+/// Runtime state (eg plugins disabled by the circuit breaker) is not known when the module is
+/// registered, so wrap the MINFO handler to display it alongside the static info.
+fn init_module_info() {
+    let name = env!("CARGO_CRATE_NAME");
+    unsafe {
+        let zv = sys::zend_hash_str_find(&raw const sys::module_registry, name.as_ptr().cast(), name.len());
+        if zv.is_null() {
+            tracing::warn!("module {} not found in module registry, runtime info disabled", name);
+            return;
+        }
+        let module_entry = (*zv).value.ptr as *mut sys::zend_module_entry;
+        UPSTREAM_MODULE_INFO = (*module_entry).info_func;
+        (*module_entry).info_func = Some(module_info);
+    }
+}
+
+unsafe extern "C" fn module_info(zend_module: *mut sys::zend_module_entry) {
+    unsafe {
+        if let Some(upstream) = UPSTREAM_MODULE_INFO {
+            upstream(zend_module);
+        }
+    }
+    let rows = get_runtime_info();
+    if rows.is_empty() {
+        return;
+    }
+    unsafe {
+        sys::php_info_print_table_start();
+        for (key, value) in rows {
+            if let (Ok(key), Ok(value)) = (CString::new(key), CString::new(value)) {
+                sys::php_info_print_table_row(2, key.as_ptr(), value.as_ptr());
+            }
+        }
+        sys::php_info_print_table_end();
+    }
+}
+
+/// Runtime state of this worker, displayed by phpinfo()
+fn get_runtime_info() -> Vec<(String, String)> {
+    let mut rows = Vec::new();
+    if let Some(plugin_manager) = auto::plugin_manager::get_global() {
+        if let Ok(pm) = plugin_manager.read() {
+            for plugin in pm.plugins() {
+                let (failures, disabled) = auto::circuit_breaker::get_plugin_health(plugin.get_name());
+                let state = if disabled {
+                    format!("disabled ({} failures)", failures)
+                } else {
+                    format!("enabled ({} failures)", failures)
+                };
+                rows.push((format!("plugin {}", plugin.get_name()), state));
+            }
+        }
+    }
+    rows.push(("tracer providers".to_string(), tracer_provider::provider_count().to_string()));
+    rows.push(("span export pipelines".to_string(), shared_pipeline::pipeline_count().to_string()));
+    rows.push(("logger providers".to_string(), logger_provider::provider_count().to_string()));
+    if ini_get::<bool>(config::ini::OTEL_ENV_DOTENV_ENABLED) {
+        rows.push(("cached .env directories".to_string(), config::dotenv::cache_size().to_string()));
+    }
+    rows
 }
 
 pub fn add_module_ini(module: &mut Module) {
@@ -77,8 +156,19 @@ pub fn add_module_ini(module: &mut Module) {
     module.add_ini(config::ini::OTEL_LOG_FILE, "/dev/stderr".to_string(), phper::ini::Policy::All);
     module.add_ini(config::ini::OTEL_CLI_CREATE_ROOT_SPAN, false, phper::ini::Policy::All);
     module.add_ini(config::ini::OTEL_CLI_ENABLED, false, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_CLI_FLUSH_INTERVAL, 10i64, phper::ini::Policy::All);
     module.add_ini(config::ini::OTEL_ENV_DOTENV_ENABLED, false, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_ENV_DOTENV_CACHE_TTL, 60i64, phper::ini::Policy::All);
     module.add_ini(config::ini::OTEL_ENV_SET_FROM_SERVER, false, phper::ini::Policy::All);
     module.add_ini(config::ini::OTEL_AUTO_ENABLED, true, phper::ini::Policy::All);
     module.add_ini(config::ini::OTEL_AUTO_DISABLED_PLUGINS, "".to_string(), phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_AUTO_PLUGIN_FAILURE_THRESHOLD, 5i64, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_PROVIDER_CACHE_MAX_SIZE, 16i64, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_PROVIDER_CACHE_IDLE_TIMEOUT, 3600i64, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_TRACES_SHARED_PIPELINE, true, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_AGENT_SOCKET, "".to_string(), phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_SPOOL_DIR, "".to_string(), phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_SPOOL_MAX_SIZE, 100i64, phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_HTTP_ROUTES, "".to_string(), phper::ini::Policy::All);
+    module.add_ini(config::ini::OTEL_HTTP_ROUTE_SCRIPT_FALLBACK, false, phper::ini::Policy::All);
 }
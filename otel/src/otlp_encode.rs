@@ -0,0 +1,107 @@
+// Encoding of spans and logs as OTLP export requests (protobuf, or OTLP-JSON), for exporters which
+// do not go through opentelemetry-otlp (agent socket, spool, file).
+use opentelemetry_proto::{
+    tonic::collector::{
+        logs::v1::ExportLogsServiceRequest,
+        trace::v1::ExportTraceServiceRequest,
+    },
+    transform::{
+        common::tonic::ResourceAttributesWithSchema,
+        logs::tonic::group_logs_by_resource_and_scope,
+        trace::tonic::group_spans_by_resource_and_scope,
+    },
+};
+use opentelemetry_sdk::{
+    logs::LogBatch,
+    trace::SpanData,
+};
+use prost::Message;
+
+pub fn spans_request(batch: Vec<SpanData>, resource: &ResourceAttributesWithSchema) -> ExportTraceServiceRequest {
+    ExportTraceServiceRequest {
+        resource_spans: group_spans_by_resource_and_scope(batch, resource),
+    }
+}
+
+/// One request for spans of several resources, with a ResourceSpans per resource (and scope).
+pub fn spans_request_by_resource<I>(batches: I) -> ExportTraceServiceRequest
+where
+    I: IntoIterator<Item = (ResourceAttributesWithSchema, Vec<SpanData>)>,
+{
+    ExportTraceServiceRequest {
+        resource_spans: batches
+            .into_iter()
+            .flat_map(|(resource, batch)| group_spans_by_resource_and_scope(batch, &resource))
+            .collect(),
+    }
+}
+
+pub fn logs_request(batch: LogBatch<'_>, resource: &ResourceAttributesWithSchema) -> ExportLogsServiceRequest {
+    ExportLogsServiceRequest {
+        resource_logs: group_logs_by_resource_and_scope(batch, resource),
+    }
+}
+
+pub fn encode_spans(batch: Vec<SpanData>, resource: &ResourceAttributesWithSchema) -> Vec<u8> {
+    spans_request(batch, resource).encode_to_vec()
+}
+
+pub fn encode_logs(batch: LogBatch<'_>, resource: &ResourceAttributesWithSchema) -> Vec<u8> {
+    logs_request(batch, resource).encode_to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::{
+        trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState},
+        InstrumentationScope, KeyValue,
+    };
+    use opentelemetry_sdk::{
+        trace::{SpanEvents, SpanLinks},
+        Resource,
+    };
+    use std::{borrow::Cow, time::SystemTime};
+
+    fn span(name: &'static str) -> SpanData {
+        SpanData {
+            span_context: SpanContext::new(TraceId::from(1), SpanId::from(1), TraceFlags::SAMPLED, false, TraceState::default()),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: Cow::Borrowed(name),
+            start_time: SystemTime::now(),
+            end_time: SystemTime::now(),
+            attributes: Vec::new(),
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: Status::Unset,
+            instrumentation_scope: InstrumentationScope::builder("test").build(),
+        }
+    }
+
+    fn resource(service_name: &'static str) -> ResourceAttributesWithSchema {
+        (&Resource::builder_empty().with_attribute(KeyValue::new("service.name", service_name)).build()).into()
+    }
+
+    #[test]
+    fn test_spans_request_by_resource() {
+        let request = spans_request_by_resource(vec![
+            (resource("one"), vec![span("a"), span("b")]),
+            (resource("two"), vec![span("c")]),
+        ]);
+        let services: Vec<_> = request
+            .resource_spans
+            .iter()
+            .map(|resource_spans| {
+                let attributes = &resource_spans.resource.as_ref().unwrap().attributes;
+                let spans: usize = resource_spans.scope_spans.iter().map(|scope_spans| scope_spans.spans.len()).sum();
+                (format!("{:?}", attributes[0].value.as_ref().unwrap().value), spans)
+            })
+            .collect();
+        assert_eq!(services.len(), 2);
+        assert!(services[0].0.contains("one") && services[0].1 == 2);
+        assert!(services[1].0.contains("two") && services[1].1 == 1);
+    }
+}
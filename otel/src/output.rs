@@ -0,0 +1,195 @@
+// Output handler injecting the root span's trace context into HTML responses, as
+// <meta name="traceparent" content="..."> after the opening <head> tag, so that browser
+// instrumentation can parent its spans to the backend request. Opt-in, via
+// OTEL_PHP_INJECT_HTML_TRACEPARENT=true. The handler is called every CHUNK_SIZE bytes; output is
+// held back until the <head> tag is found, or until MAX_BUFFERED bytes, a flush or the end of the
+// request, after which it passes through unchanged. Non-HTML, compressed and fixed-length
+// responses are not modified.
+use crate::sapi;
+use phper::{sg, sys};
+use std::{
+    cell::RefCell,
+    ffi::{CStr, c_int, c_void},
+};
+
+const HANDLER_NAME: &str = "otel traceparent meta";
+const CHUNK_SIZE: usize = 4096;
+const MAX_BUFFERED: usize = 64 * 1024;
+
+enum State {
+    Buffering {
+        meta: String,
+        buffer: Vec<u8>,
+    },
+    Done,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const { RefCell::new(State::Done) };
+    /// The handler's output, which must stay alive until the output layer has written it.
+    static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Start the output handler for the current request, injecting `traceparent`.
+pub fn start(traceparent: &str) {
+    STATE.with(|state| {
+        *state.borrow_mut() = State::Buffering {
+            meta: format!("<meta name=\"traceparent\" content=\"{}\">", traceparent),
+            buffer: Vec::new(),
+        }
+    });
+    unsafe {
+        let handler = sys::php_output_handler_create_internal(
+            HANDLER_NAME.as_ptr().cast(),
+            HANDLER_NAME.len(),
+            Some(handler),
+            CHUNK_SIZE,
+            sys::PHP_OUTPUT_HANDLER_STDFLAGS as c_int,
+        );
+        if handler.is_null() || sys::php_output_handler_start(handler) != 0 {
+            tracing::warn!("Failed to start traceparent output handler");
+            STATE.with(|state| *state.borrow_mut() = State::Done);
+        }
+    }
+}
+
+/// Insert `meta` after the opening <head> tag, if there is one.
+fn inject(html: &[u8], meta: &str) -> Option<Vec<u8>> {
+    let lower = html.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(pos) = find(&lower[from..], b"<head").map(|pos| pos + from) {
+        let next = lower.get(pos + 5).copied();
+        if matches!(next, Some(b'>' | b' ' | b'\t' | b'\r' | b'\n' | b'/')) {
+            let end = pos + find(&lower[pos..], b">")? + 1;
+            let mut injected = Vec::with_capacity(html.len() + meta.len());
+            injected.extend_from_slice(&html[..end]);
+            injected.extend_from_slice(meta.as_bytes());
+            injected.extend_from_slice(&html[end..]);
+            return Some(injected);
+        }
+        from = pos + 5;
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Whether the response is HTML which can be modified: not yet sent, text/html (explicitly or by
+/// default_mimetype), and neither compressed nor with a fixed Content-Length.
+fn is_modifiable_html() -> bool {
+    let (headers_sent, default_mimetype) = unsafe { (sg!(headers_sent), sg!(default_mimetype)) };
+    if headers_sent != Default::default() {
+        return false;
+    }
+    let mut content_type = None;
+    for line in sapi::response_headers() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("content-encoding") || name.eq_ignore_ascii_case("content-length") {
+            return false;
+        }
+        if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.trim().to_ascii_lowercase());
+        }
+    }
+    let content_type = content_type.unwrap_or_else(|| {
+        if default_mimetype.is_null() {
+            "text/html".to_string()
+        } else {
+            unsafe { CStr::from_ptr(default_mimetype) }.to_string_lossy().to_ascii_lowercase()
+        }
+    });
+    content_type.starts_with("text/html")
+}
+
+/// What to do with a chunk of output.
+enum Action {
+    Pass,
+    Hold,
+    Write(Vec<u8>),
+}
+
+fn process(input: &[u8], op: c_int) -> Action {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let State::Buffering { meta, buffer } = &mut *state else {
+            return Action::Pass;
+        };
+        if op & sys::PHP_OUTPUT_HANDLER_CLEAN as c_int != 0 {
+            // ob_clean/ob_end_clean: the output so far is discarded
+            buffer.clear();
+            return Action::Hold;
+        }
+        buffer.extend_from_slice(input);
+        let last = op & (sys::PHP_OUTPUT_HANDLER_FLUSH | sys::PHP_OUTPUT_HANDLER_FINAL) as c_int != 0;
+        if !is_modifiable_html() {
+            let buffered = std::mem::take(buffer);
+            *state = State::Done;
+            return Action::Write(buffered);
+        }
+        if let Some(injected) = inject(buffer, meta) {
+            *state = State::Done;
+            return Action::Write(injected);
+        }
+        if last || buffer.len() > MAX_BUFFERED {
+            let buffered = std::mem::take(buffer);
+            *state = State::Done;
+            return Action::Write(buffered);
+        }
+        Action::Hold
+    })
+}
+
+unsafe extern "C" fn handler(_handler_context: *mut *mut c_void, output_context: *mut sys::php_output_context) -> c_int {
+    unsafe {
+        let context = &mut *output_context;
+        let input: &[u8] = if context.in_.data.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(context.in_.data as *const u8, context.in_.used)
+        };
+        match process(input, context.op) {
+            Action::Pass => {
+                // output the input buffer as-is; it is still owned (and freed) by the input
+                context.out.data = context.in_.data;
+                context.out.used = context.in_.used;
+                context.out.size = context.in_.size;
+            }
+            Action::Hold => {
+                context.out.data = std::ptr::null_mut();
+                context.out.used = 0;
+                context.out.size = 0;
+            }
+            Action::Write(data) => OUTPUT.with(|output| {
+                let mut output = output.borrow_mut();
+                *output = data;
+                context.out.data = output.as_mut_ptr().cast();
+                context.out.used = output.len();
+                context.out.size = output.capacity();
+            }),
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject() {
+        let meta = "<meta name=\"traceparent\" content=\"00-abc-def-01\">";
+        let html = b"<!DOCTYPE html><html><header></header><HEAD lang=\"en\"><title>x</title></head></html>";
+        let injected = inject(html, meta).unwrap();
+        assert_eq!(
+            String::from_utf8(injected).unwrap(),
+            "<!DOCTYPE html><html><header></header><HEAD lang=\"en\"><meta name=\"traceparent\" content=\"00-abc-def-01\"><title>x</title></head></html>"
+        );
+        assert!(inject(b"<html><body>no head</body></html>", meta).is_none());
+        assert!(inject(b"<html><head", meta).is_none());
+    }
+}
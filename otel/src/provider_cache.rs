@@ -0,0 +1,273 @@
+// Bounded cache of tracer/logger providers, keyed by (pid, config key). Each provider owns an
+// export thread and exporter connection, so a worker serving many apps must not keep one for every
+// configuration it has ever seen. Providers which have been idle for too long, or are the least
+// recently used when the cache is full, are evicted, then flushed and shut down. Providers leased by
+// a request (see acquire) are never evicted: its tracers and spans hold clones of the provider, so
+// shutting it down from another thread would lose their spans.
+use crate::config;
+use phper::ini::ini_get;
+use opentelemetry_sdk::{
+    logs::SdkLoggerProvider,
+    trace::SdkTracerProvider,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+pub type ProviderKey = (u32, String);
+
+/// A provider which can be flushed and shut down when evicted.
+pub trait CachedProvider {
+    fn flush(&self);
+    fn shutdown(&self);
+}
+
+impl CachedProvider for SdkTracerProvider {
+    fn flush(&self) {
+        if let Err(err) = self.force_flush() {
+            tracing::warn!("Failed to flush evicted tracer provider: {:?}", err);
+        }
+    }
+    fn shutdown(&self) {
+        if let Err(err) = SdkTracerProvider::shutdown(self) {
+            tracing::warn!("Failed to shut down evicted tracer provider: {:?}", err);
+        }
+    }
+}
+
+impl CachedProvider for SdkLoggerProvider {
+    fn flush(&self) {
+        if let Err(err) = self.force_flush() {
+            tracing::warn!("Failed to flush evicted logger provider: {:?}", err);
+        }
+    }
+    fn shutdown(&self) {
+        if let Err(err) = SdkLoggerProvider::shutdown(self) {
+            tracing::warn!("Failed to shut down evicted logger provider: {:?}", err);
+        }
+    }
+}
+
+struct Entry<P> {
+    provider: Arc<P>,
+    last_used: Instant,
+    leases: usize,
+}
+
+pub struct ProviderCache<P> {
+    entries: HashMap<ProviderKey, Entry<P>>,
+}
+
+/// Cache limits, from ini settings.
+pub struct CacheLimits {
+    pub max_size: usize,
+    pub idle_timeout: Option<Duration>,
+}
+
+impl CacheLimits {
+    pub fn from_ini() -> Self {
+        let max_size = ini_get::<i64>(config::ini::OTEL_PROVIDER_CACHE_MAX_SIZE).max(1) as usize;
+        let idle_timeout = ini_get::<i64>(config::ini::OTEL_PROVIDER_CACHE_IDLE_TIMEOUT);
+        Self {
+            max_size,
+            idle_timeout: (idle_timeout > 0).then(|| Duration::from_secs(idle_timeout as u64)),
+        }
+    }
+}
+
+impl<P: CachedProvider> ProviderCache<P> {
+    pub fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn contains_key(&self, key: &ProviderKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Get a provider, marking it as recently used.
+    pub fn get(&mut self, key: &ProviderKey) -> Option<Arc<P>> {
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_used = Instant::now();
+            entry.provider.clone()
+        })
+    }
+
+    /// Lease a provider, so that it is not evicted until released. Returns false if not cached.
+    pub fn acquire(&mut self, key: &ProviderKey) -> bool {
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_used = Instant::now();
+            entry.leases += 1;
+        }).is_some()
+    }
+
+    /// Release a lease taken by acquire. The provider's idle time starts now.
+    pub fn release(&mut self, key: &ProviderKey) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_used = Instant::now();
+            entry.leases = entry.leases.saturating_sub(1);
+        }
+    }
+
+    /// Get a provider without marking it as used.
+    pub fn peek(&self, key: &ProviderKey) -> Option<&Arc<P>> {
+        self.entries.get(key).map(|entry| &entry.provider)
+    }
+
+    /// Insert a provider, returning any providers evicted to make room (or for being idle). The
+    /// caller should shut them down (see shutdown_evicted) after releasing its lock.
+    pub fn insert(&mut self, key: ProviderKey, provider: Arc<P>, limits: &CacheLimits) -> Vec<(ProviderKey, Arc<P>)> {
+        let now = Instant::now();
+        let mut evicted = self.evict_idle(limits, now);
+        while self.entries.len() >= limits.max_size {
+            // least recently used provider which is not leased
+            let lru = self.entries
+                .iter()
+                .filter(|(_, entry)| entry.leases == 0)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match lru.and_then(|key| self.entries.remove_entry(&key)) {
+                Some((key, entry)) => evicted.push((key, entry.provider)),
+                None => break,
+            }
+        }
+        self.entries.insert(key, Entry { provider, last_used: now, leases: 0 });
+        evicted
+    }
+
+    /// Remove providers which have not been used within the idle timeout.
+    pub fn evict_idle(&mut self, limits: &CacheLimits, now: Instant) -> Vec<(ProviderKey, Arc<P>)> {
+        let Some(idle_timeout) = limits.idle_timeout else {
+            return Vec::new();
+        };
+        let idle: Vec<_> = self.entries
+            .iter()
+            .filter(|(_, entry)| entry.leases == 0 && now.duration_since(entry.last_used) >= idle_timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        idle.into_iter()
+            .filter_map(|key| self.entries.remove_entry(&key).map(|(key, entry)| (key, entry.provider)))
+            .collect()
+    }
+
+    /// All providers for which the predicate is true, without marking them as used.
+    pub fn providers_where<F: Fn(&ProviderKey) -> bool>(&self, predicate: F) -> Vec<Arc<P>> {
+        self.entries
+            .iter()
+            .filter(|(key, _)| predicate(key))
+            .map(|(_, entry)| entry.provider.clone())
+            .collect()
+    }
+
+    /// Remove all providers for which the predicate is true.
+    pub fn remove_where<F: Fn(&ProviderKey) -> bool>(&mut self, predicate: F) -> Vec<(ProviderKey, Arc<P>)> {
+        let keys: Vec<_> = self.entries.keys().filter(|key| predicate(key)).cloned().collect();
+        keys.into_iter()
+            .filter_map(|key| self.entries.remove_entry(&key).map(|(key, entry)| (key, entry.provider)))
+            .collect()
+    }
+}
+
+/// Flush and shut down evicted providers.
+pub fn shutdown_evicted<P: CachedProvider>(kind: &str, evicted: Vec<(ProviderKey, Arc<P>)>) {
+    for (key, provider) in evicted {
+        tracing::debug!("Evicting {} provider for key {:?}", kind, key);
+        provider.flush();
+        provider.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct TestProvider {
+        shutdowns: AtomicUsize,
+    }
+
+    impl CachedProvider for TestProvider {
+        fn flush(&self) {}
+        fn shutdown(&self) {
+            self.shutdowns.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn key(name: &str) -> ProviderKey {
+        (1, name.to_string())
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let limits = CacheLimits { max_size: 2, idle_timeout: None };
+        let mut cache = ProviderCache::new();
+        assert!(cache.insert(key("a"), Arc::new(TestProvider::default()), &limits).is_empty());
+        assert!(cache.insert(key("b"), Arc::new(TestProvider::default()), &limits).is_empty());
+        cache.get(&key("a"));
+        let evicted = cache.insert(key("c"), Arc::new(TestProvider::default()), &limits);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0, key("b"));
+        assert!(cache.contains_key(&key("a")));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_does_not_evict_leased_provider() {
+        let limits = CacheLimits { max_size: 1, idle_timeout: Some(Duration::from_secs(60)) };
+        let mut cache = ProviderCache::new();
+        cache.insert(key("a"), Arc::new(TestProvider::default()), &limits);
+        assert!(cache.acquire(&key("a")));
+        // the request's tracers hold their own clones of the provider, not the cached Arc
+        drop(cache.get(&key("a")));
+        assert!(cache.evict_idle(&limits, Instant::now() + Duration::from_secs(61)).is_empty());
+        let evicted = cache.insert(key("b"), Arc::new(TestProvider::default()), &limits);
+        assert!(evicted.is_empty());
+        assert_eq!(cache.len(), 2);
+
+        cache.release(&key("a"));
+        let evicted = cache.insert(key("c"), Arc::new(TestProvider::default()), &limits);
+        assert_eq!(evicted.len(), 2);
+        assert!(cache.contains_key(&key("c")));
+    }
+
+    #[test]
+    fn test_unleased_provider_is_evicted_while_referenced() {
+        let limits = CacheLimits { max_size: 1, idle_timeout: None };
+        let mut cache = ProviderCache::new();
+        cache.insert(key("a"), Arc::new(TestProvider::default()), &limits);
+        let _clone = cache.get(&key("a"));
+        let evicted = cache.insert(key("b"), Arc::new(TestProvider::default()), &limits);
+        assert_eq!(evicted.len(), 1);
+        assert!(!cache.acquire(&key("a")));
+    }
+
+    #[test]
+    fn test_evicts_idle() {
+        let limits = CacheLimits { max_size: 10, idle_timeout: Some(Duration::from_secs(60)) };
+        let mut cache = ProviderCache::new();
+        cache.insert(key("a"), Arc::new(TestProvider::default()), &limits);
+        let evicted = cache.evict_idle(&limits, Instant::now() + Duration::from_secs(61));
+        assert_eq!(evicted.len(), 1);
+        shutdown_evicted("test", evicted.iter().map(|(k, p)| (k.clone(), p.clone())).collect());
+        assert_eq!(evicted[0].1.shutdowns.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_providers_where() {
+        let limits = CacheLimits { max_size: 10, idle_timeout: None };
+        let mut cache = ProviderCache::new();
+        cache.insert(key("a"), Arc::new(TestProvider::default()), &limits);
+        cache.insert(key("b"), Arc::new(TestProvider::default()), &limits);
+        cache.insert((2, "c".to_string()), Arc::new(TestProvider::default()), &limits);
+        assert_eq!(cache.providers_where(|(pid, _)| *pid == 1).len(), 2);
+        assert_eq!(cache.providers_where(|(pid, _)| *pid == 3).len(), 0);
+        assert_eq!(cache.len(), 3);
+    }
+}
@@ -8,43 +8,53 @@ use phper::{
     arrays::{IterKey, ZArr},
     values::ZVal,
 };
-use once_cell::sync::Lazy;
 use std::{
     cell::RefCell,
     collections::HashMap,
-    env,
-    fs,
-    path::{Path, PathBuf},
     sync::Arc,
-    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
 };
 use opentelemetry::{
     global,
     Context,
     InstrumentationScope,
     KeyValue,
-    trace::{SpanKind, Tracer, TraceContextExt, TracerProvider},
+    propagation::{TextMapCompositePropagator, TextMapPropagator},
+    trace::{Span as _, SpanKind, SpanRef, Tracer, TraceContextExt, TracerProvider},
 };
-use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
 use opentelemetry_semantic_conventions as SemConv;
 use crate::{
     auto,
-    config,
+    config::{self, http_headers::{HeaderCapture, Side}},
     context::storage,
     logging,
     logs::logger_provider,
     module,
+    output,
+    sapi,
     error::php_error_to_attributes,
+    finish_request,
+    fork,
     trace::{local_root_span, tracer_provider},
     util::{get_sapi_module_name},
 };
 
+mod cli;
+mod exclusion;
+mod http;
+mod parse_cache;
+mod route;
+
 thread_local! {
     static OTEL_REQUEST_GUARD: RefCell<Option<opentelemetry::ContextGuard>> = RefCell::new(None);
     static OTEL_CONTEXT_ID: RefCell<Option<u64>> = RefCell::new(None);
+    /// The root span's context, once ended by fastcgi_finish_request (or litespeed_finish_request).
+    static FINISHED_CONTEXT_ID: RefCell<Option<u64>> = RefCell::new(None);
+    //backup mutating environment variables for request duration
+    static ENV_BACKUP: RefCell<Option<HashMap<String, String>>> = RefCell::new(None);
+    static LAST_FLUSH: RefCell<Option<Instant>> = RefCell::new(None);
 }
-//backup mutating environment variables for request duration
-static ENV_BACKUP: Lazy<Mutex<HashMap<u32, HashMap<String, String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
 /// RINIT handler.
 pub fn on_request_init() {
@@ -63,17 +73,59 @@ pub fn on_request_init() {
 
     tracer_provider::init_once();
     logger_provider::init_once();
-    global::set_text_map_propagator(TraceContextPropagator::new());
+    init_propagator();
+    fork::init_once();
+    finish_request::init_once();
 
     init();
 }
 
-/// RSHUTDOWN handler. Invoke request shutdown logic, call shutdown() on plugin manager.
+/// Start of a FrankenPHP worker request (one iteration of frankenphp_handle_request). RINIT and
+/// RSHUTDOWN only run once for the lifetime of a worker script, so each handled request gets its
+/// own environment and root span here.
+pub fn on_worker_request_init() {
+    if module::is_disabled() {
+        return;
+    }
+    tracing::debug!("OpenTelemetry::worker request init");
+    init_environment();
+
+    if is_disabled() {
+        tracing::debug!("OpenTelemetry::worker request init: SDK disabled, skipping initialization");
+        return;
+    }
+
+    tracer_provider::init_once();
+    logger_provider::init_once();
+    init_propagator();
+
+    start_root_span(None);
+}
+
+/// End of a FrankenPHP worker request.
+pub fn on_worker_request_shutdown() {
+    if module::is_disabled() {
+        return;
+    }
+    tracing::debug!("OpenTelemetry::worker request shutdown");
+    end_request();
+}
+
+/// RSHUTDOWN handler. Invoke request shutdown logic, then release the request's providers. A
+/// worker script's tracers may be used by every request it handles, so its providers stay leased
+/// until the script ends.
 pub fn on_request_shutdown() {
     if module::is_disabled() {
         return;
     }
     tracing::debug!("OpenTelemetry::RSHUTDOWN");
+    end_request();
+    tracer_provider::release_leases();
+    logger_provider::release_leases();
+}
+
+/// End the root span and call shutdown() on plugin manager.
+fn end_request() {
     shutdown();
     if let Some(plugin_manager) = auto::plugin_manager::get_global() {
         let pm = plugin_manager.read().expect("Failed to acquire read lock");
@@ -81,7 +133,7 @@ pub fn on_request_shutdown() {
     }
 }
 
-/// Check if OpenTelemetry is disabled for the current request (by env, .env or excluded URL)
+/// Check if OpenTelemetry is disabled for the current request (by env, .env or exclusion rules)
 pub fn is_disabled() -> bool {
     let request_details = get_request_details();
 
@@ -92,7 +144,11 @@ pub fn is_disabled() -> bool {
             return true;
         }
     }
-    match std::env::var("OTEL_SDK_DISABLED") {
+    if let Some(rule) = exclusion::excluded_by(request_details.uri.as_deref(), request_details.method.as_deref()) {
+        tracing::debug!("RINIT::excluded request, {} rule matched", rule);
+        return true;
+    }
+    match config::env::var("OTEL_SDK_DISABLED") {
         Ok(val) => val == "true",
         Err(_) => false,
     }
@@ -156,8 +212,8 @@ fn init_environment() {
 fn set_env_from_server() {
     let otel_vars = get_server_vars_with_prefix("OTEL_");
     for (k, v) in otel_vars {
-        unsafe { std::env::set_var(&k, &v) };
-        tracing::debug!("Set environment variable from $_SERVER: {}={}", k, v);
+        config::env::set_var(&k, &v);
+        tracing::debug!("Set environment variable from $_SERVER: {}={}", k, loggable_value(&k, &v));
     }
 }
 
@@ -165,108 +221,47 @@ fn set_env_from_server() {
 /// variables found there into the process environment (OTEL_SERVICE_NAME,
 /// OTEL_RESOURCE_ATTRIBUTES, OTEL_SDK_DISABLED).
 fn process_dotenv() {
-    if let Some(env_path) = find_dotenv() {
-        tracing::debug!("Discovered .env path: {:?}", env_path);
-        let mut service_name = None;
-        let mut resource_attributes = None;
-        let mut otel_disabled = None;
-        if let Ok(iter) = dotenvy::from_path_iter(&env_path) {
-            for item in iter.flatten() {
-                match item.0.as_str() {
-                    "OTEL_SERVICE_NAME" => service_name = Some(item.1),
-                    "OTEL_RESOURCE_ATTRIBUTES" => resource_attributes = Some(item.1),
-                    "OTEL_SDK_DISABLED" => otel_disabled = Some(item.1),
-                    _ => {}
-                }
-            }
-            //now we _might_ have service name and resource attributes
-            let mut env = HashMap::new();
-            if let Some(service_name) = service_name {
-                env.insert("OTEL_SERVICE_NAME".to_string(), service_name);
-            }
-            if let Some(otel_disabled) = otel_disabled {
-                env.insert("OTEL_SDK_DISABLED".to_string(), otel_disabled);
-            }
-            if let Some(resource_attributes) = resource_attributes {
-                //merge with original env var, if it exists
-                let mut merged = if let Some(existing) = std::env::var("OTEL_RESOURCE_ATTRIBUTES").ok() {
-                    parse_resource_attributes(&existing)
-                } else {
-                    HashMap::new()
-                };
-
-                // Overwrite with values from dotenv
-                for (k, v) in parse_resource_attributes(&resource_attributes) {
-                    merged.insert(k, v);
-                }
+    let dotenv = get_server_var("SCRIPT_FILENAME")
+        .and_then(|script_filename| config::dotenv::load(&script_filename, get_server_var("DOCUMENT_ROOT").as_deref()));
+    if let Some(dotenv) = dotenv {
+        tracing::debug!("Discovered .env path: {:?}", dotenv.path);
+        let mut env: HashMap<String, String> = dotenv.vars.iter().cloned().collect();
+        if let Some(resource_attributes) = env.remove("OTEL_RESOURCE_ATTRIBUTES") {
+            //merge with original env var, if it exists
+            let mut merged = if let Some(existing) = config::env::var("OTEL_RESOURCE_ATTRIBUTES").ok() {
+                parse_resource_attributes(&existing)
+            } else {
+                HashMap::new()
+            };
 
-                // Serialize back to comma-separated key=value pairs
-                let merged_str = {
-                    let mut items: Vec<_> = merged.into_iter().collect();
-                    items.sort_by(|a, b| a.0.cmp(&b.0));
-                    items
-                        .into_iter()
-                        .map(|(k, v)| format!("{}={}", k, v))
-                        .collect::<Vec<_>>()
-                        .join(",")
-                };
-
-                env.insert("OTEL_RESOURCE_ATTRIBUTES".to_string(), merged_str);
+            // Overwrite with values from dotenv
+            for (k, v) in parse_resource_attributes(&resource_attributes) {
+                merged.insert(k, v);
             }
-            set_request_dotenv(env);
-        }
-    } else {
-        tracing::warn!("No .env file found between SCRIPT_FILENAME and DOCUMENT_ROOT");
-    }
-}
 
-/// Find the nearest .env file starting from the script's directory, finishing at DOCUMENT_ROOT.
-/// If DOCUMENT_ROOT is not set, it will only look in the script's directory.
-fn find_dotenv() -> Option<PathBuf> {
-    let script_filename = get_server_var("SCRIPT_FILENAME")?;
-    let script_dir = Path::new(&script_filename).parent()?;
-    let env_in_dir = |dir: &Path| {
-        let env_path = dir.join(".env");
-        fs::metadata(&env_path).ok().map(|_| env_path)
-    };
-
-    match get_server_var("DOCUMENT_ROOT") {
-        Some(document_root) => {
-            if document_root.is_empty() {
-                return env_in_dir(script_dir);
-            }
-            let docroot = match Path::new(&document_root).canonicalize() {
-                Ok(path) => path,
-                Err(err) => {
-                    tracing::warn!("Failed to canonicalize DOCUMENT_ROOT '{}': {}", document_root, err);
-                    return env_in_dir(script_dir);
-                },
-            };
-            let mut current = match script_dir.canonicalize() {
-                Ok(path) => path,
-                Err(_) => return None,
+            // Serialize back to comma-separated key=value pairs
+            let merged_str = {
+                let mut items: Vec<_> = merged.into_iter().collect();
+                items.sort_by(|a, b| a.0.cmp(&b.0));
+                items
+                    .into_iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect::<Vec<_>>()
+                    .join(",")
             };
-            loop {
-                if let Some(env_path) = env_in_dir(&current) {
-                    return Some(env_path);
-                }
-                if current == docroot {
-                    break;
-                }
-                if !current.pop() {
-                    break;
-                }
-            }
-            None
+
+            env.insert("OTEL_RESOURCE_ATTRIBUTES".to_string(), merged_str);
         }
-        None => env_in_dir(script_dir),
+        set_request_dotenv(env);
+    } else {
+        tracing::warn!("No .env file found between SCRIPT_FILENAME and DOCUMENT_ROOT");
     }
 }
 
 /// Returns true if the given URI matches any pattern in the excluded URLs list.
 /// Patterns support '*' as a wildcard (prefix/suffix/anywhere).
 fn is_excluded_url(uri: &str) -> bool {
-    match std::env::var("OTEL_PHP_EXCLUDED_URLS") {
+    match config::env::var("OTEL_PHP_EXCLUDED_URLS") {
         Ok(list) => {
             tracing::debug!("is_excluded_url: OTEL_PHP_EXCLUDED_URLS='{}'", list);
             list.split(',')
@@ -313,6 +308,21 @@ fn is_excluded_url(uri: &str) -> bool {
     }
 }
 
+/// Set the global propagator from OTEL_PROPAGATORS (tracecontext, baggage or none), default tracecontext.
+fn init_propagator() {
+    let names = config::env::var("OTEL_PROPAGATORS").unwrap_or_else(|_| "tracecontext".to_string());
+    let mut propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>> = Vec::new();
+    for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        match name {
+            "tracecontext" => propagators.push(Box::new(TraceContextPropagator::new())),
+            "baggage" => propagators.push(Box::new(BaggagePropagator::new())),
+            "none" => {}
+            other => tracing::warn!("Unsupported propagator {}, ignoring", other),
+        }
+    }
+    global::set_text_map_propagator(TextMapCompositePropagator::new(propagators));
+}
+
 /// Initialize the request handler, creating a root span if necessary.
 fn init() {
     tracing::debug!("RINIT::initializing request handler");
@@ -328,17 +338,38 @@ fn init() {
         let trace_cli = ini_get::<bool>(config::ini::OTEL_CLI_CREATE_ROOT_SPAN);
         if trace_cli {
             tracing::debug!("RINIT::tracing cli enabled by ini");
-            span_name = Some("php:cli".to_string());
+            span_name = Some(cli::root_span_name());
         } else {
             tracing::debug!("RINIT::not auto-creating root span...");
             return;
         }
     }
+    if is_frankenphp_worker() {
+        tracing::debug!("RINIT::FrankenPHP worker script, root spans are created per handled request");
+        return;
+    }
+    start_root_span(span_name);
+}
+
+/// Whether this is a FrankenPHP worker script, which handles many requests via
+/// frankenphp_handle_request
+fn is_frankenphp_worker() -> bool {
+    get_sapi_module_name() == "frankenphp" && get_server_var("FRANKENPHP_WORKER").is_some()
+}
+
+/// Create the root span for a request, and attach its context.
+fn start_root_span(mut span_name: Option<String>) {
     let request_details = get_request_details();
+    let mut route: Option<String> = None;
     if span_name.is_none() {
-        span_name = match &request_details.method {
-            Some(method) => Some(format!("{}", method)),
-            None => Some("<unknown>".to_string()),
+        if get_sapi_module_name() != "cli" {
+            let path = request_details.uri.as_deref().map(|uri| uri.split('?').next().unwrap_or_default());
+            route = route::resolve(path.unwrap_or_default(), get_server_var("SCRIPT_NAME"));
+        }
+        span_name = match (&request_details.method, &route) {
+            (Some(method), Some(route)) => Some(format!("{} {}", method, route)),
+            (Some(method), None) => Some(format!("{}", method)),
+            (None, _) => Some("<unknown>".to_string()),
         };
     }
 
@@ -347,18 +378,50 @@ fn init() {
     let scope = InstrumentationScope::builder("php:rinit").build();
     let tracer = tracer_provider.tracer_with_scope(scope);
     let span_builder = tracer.span_builder(span_name.unwrap_or("unknown".to_string()));
+    let request_time = if get_sapi_module_name() != "cli" && !is_frankenphp_worker() {
+        get_server_float("REQUEST_TIME_FLOAT")
+    } else {
+        None
+    };
     let mut attributes = span_builder.attributes.clone().unwrap_or_default();
-    attributes.push(KeyValue::new(SemConv::trace::URL_FULL, request_details.uri.unwrap_or_default()));
-    attributes.push(KeyValue::new(SemConv::trace::HTTP_REQUEST_METHOD, request_details.method.unwrap_or_default()));
-    //attributes.push(KeyValue::new(SemConv::trace::HTTP_REQUEST_BODY_SIZE, request_details.body_length.unwrap_or_default())); //experimental
-    // TODO other HTTP attributes are experimental
+    if get_sapi_module_name() == "cli" {
+        attributes.extend(cli::attributes());
+    } else {
+        attributes.push(KeyValue::new(SemConv::trace::URL_FULL, request_details.uri.unwrap_or_default()));
+        attributes.push(KeyValue::new(SemConv::trace::HTTP_REQUEST_METHOD, request_details.method.unwrap_or_default()));
+        attributes.extend(http::request_attributes(request_details.body_length));
+        attributes.extend(http::request_header_attributes(&HeaderCapture::from_config(Side::Server)));
+        if let Some(route) = route {
+            attributes.push(KeyValue::new(SemConv::trace::HTTP_ROUTE, route));
+        }
+        if let Some(request_time) = request_time {
+            attributes.extend(http::queue_time_attribute(request_time));
+        }
+        sapi::reset_body_size();
+    }
 
     let mut span_builder = span_builder.clone().with_attributes(attributes);
     span_builder.span_kind = Some(SpanKind::Server);
+    span_builder.start_time = request_time.and_then(|request_time| http::request_start_time(request_time, SystemTime::now()));
     let parent_context = get_propagated_context();
     let is_local_root = !Context::current().span().span_context().is_valid();
     let span = tracer.build_with_context(span_builder, &parent_context);
-    let ctx = Context::current_with_span(span);
+    if get_sapi_module_name() != "cli" {
+        sapi::set_pending_headers(http::trace_response_headers(span.span_context()));
+        if span.span_context().is_valid()
+            && !is_frankenphp_worker()
+            && config::env::var("OTEL_PHP_INJECT_HTML_TRACEPARENT").is_ok_and(|value| value == "true")
+        {
+            output::start(&http::traceparent(span.span_context()));
+        }
+    }
+    attach_root_span(Context::current_with_span(span), is_local_root);
+    tracing::debug!("RINIT::request initialized");
+}
+
+/// Store and attach the context containing a root span, so that it is ended by RSHUTDOWN (or
+/// LocalRootSpan::end) if not before.
+fn attach_root_span(ctx: Context, is_local_root: bool) -> Option<u64> {
     let context_id = storage::store_context_instance(Arc::new(ctx.clone()));
     OTEL_CONTEXT_ID.with(|cell| {
         *cell.borrow_mut() = context_id;
@@ -372,49 +435,210 @@ fn init() {
     OTEL_REQUEST_GUARD.with(|slot| {
         *slot.borrow_mut() = Some(guard);
     });
-    tracing::debug!("RINIT::request initialized");
+    context_id
 }
 
-/// Shutdown the request handler, closing the root span if it exists.
-/// Restore the environment variables to their original state.
+/// Start a root span for one unit of work (eg a job) in a long-running worker. Any root span
+/// which is still active, including one created by otel.cli.create_root_span, is ended first.
+/// Returns the id of the stored context containing the new span.
+pub fn start_job_span(name: String, carrier: HashMap<String, String>, kind: SpanKind) -> Option<u64> {
+    end_job_span();
+    tracing::debug!("starting job root span, name={}", name);
+    let parent_context = global::get_text_map_propagator(|prop| prop.extract(&carrier));
+    let tracer_provider = tracer_provider::get_tracer_provider();
+    let scope = InstrumentationScope::builder("php:worker").build();
+    let tracer = tracer_provider.tracer_with_scope(scope);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(kind)
+        .start_with_context(&tracer, &parent_context);
+    attach_root_span(Context::current_with_span(span), true)
+}
+
+/// End the current job root span (if any), then run the same cleanup as RSHUTDOWN: plugin request
+/// shutdown, and a reset of context storage. Exports are flushed if the flush interval has elapsed.
+pub fn end_job_span() {
+    if OTEL_CONTEXT_ID.with(|cell| cell.borrow().is_none()) {
+        return;
+    }
+    tracing::debug!("ending job root span");
+    end_root_span(false);
+    local_root_span::store_local_root_span(None);
+    if let Some(plugin_manager) = auto::plugin_manager::get_global() {
+        let pm = plugin_manager.read().expect("Failed to acquire read lock");
+        pm.request_shutdown();
+    }
+    maybe_flush();
+}
+
+/// In a forked child, replace the root span inherited from the parent (which the parent will end
+/// and export) with a new root span, whose parent is the parent process's current span.
+pub fn on_fork_child() {
+    let Some(context_id) = OTEL_CONTEXT_ID.with(|cell| cell.borrow_mut().take()) else {
+        return;
+    };
+    let parent_context = Context::current();
+    if let Some(ctx) = storage::get_context_instance(Some(context_id)) {
+        // Never drop the inherited span, since dropping an SDK span ends it
+        std::mem::forget((*ctx).clone());
+    }
+    OTEL_REQUEST_GUARD.with(|slot| {
+        *slot.borrow_mut() = None;
+    });
+    storage::remove_context_instance(context_id);
+
+    let tracer_provider = tracer_provider::get_tracer_provider();
+    let scope = InstrumentationScope::builder("php:fork").build();
+    let tracer = tracer_provider.tracer_with_scope(scope);
+    let span = tracer
+        .span_builder("php:fork")
+        .with_kind(SpanKind::Internal)
+        .start_with_context(&tracer, &parent_context);
+    tracing::debug!("fork: started child root span");
+    attach_root_span(Context::current_with_span(span), true);
+}
+
+/// Flush tracer and logger providers, if at least otel.cli.flush_interval seconds have passed
+/// since the last flush.
+fn maybe_flush() {
+    let interval = ini_get::<i64>(config::ini::OTEL_CLI_FLUSH_INTERVAL).max(0) as u64;
+    let now = Instant::now();
+    let due = LAST_FLUSH.with(|cell| {
+        let mut last_flush = cell.borrow_mut();
+        match *last_flush {
+            Some(last) if now.duration_since(last) < Duration::from_secs(interval) => false,
+            None if interval > 0 => {
+                *last_flush = Some(now);
+                false
+            }
+            _ => {
+                *last_flush = Some(now);
+                true
+            }
+        }
+    });
+    if due {
+        tracer_provider::force_flush();
+        logger_provider::force_flush();
+    }
+}
+
+/// Shutdown the request handler, closing the root span if it exists (while the request's
+/// configuration is still available). Restore the environment variables to their original state.
 fn shutdown() {
+    let is_cli = get_sapi_module_name() == "cli";
+    if is_cli {
+        record_cli_exit();
+    }
+    end_root_span(!is_cli);
     restore_env();
+}
+
+/// The response has been sent by fastcgi_finish_request (or litespeed_finish_request): record it
+/// on the root span and end it, export what the request has buffered so far (which no longer delays
+/// the response), and continue the request in a post-response span, which is ended by RSHUTDOWN.
+pub fn on_finish_request() {
+    if FINISHED_CONTEXT_ID.with(|cell| cell.borrow().is_some()) {
+        return;
+    }
+    let Some(context_id) = OTEL_CONTEXT_ID.with(|cell| *cell.borrow()) else {
+        return;
+    };
+    let Some(ctx) = storage::get_context_instance(Some(context_id)) else {
+        return;
+    };
+    let span = ctx.span();
+    if !span.span_context().is_valid() {
+        return;
+    }
+    tracing::debug!("finish_request: ending root span, starting post-response span");
+    if get_sapi_module_name() != "cli" {
+        record_http_response(&span);
+    }
+    span.end();
+    tracer_provider::force_flush();
+    logger_provider::force_flush();
+
+    let tracer_provider = tracer_provider::get_tracer_provider();
+    let scope = InstrumentationScope::builder("php:rinit").build();
+    let tracer = tracer_provider.tracer_with_scope(scope);
+    let post_response = tracer
+        .span_builder("post-response")
+        .with_kind(SpanKind::Internal)
+        .start_with_context(&tracer, &ctx);
+    let post_response_ctx = ctx.with_span(post_response);
+    drop(ctx);
+    FINISHED_CONTEXT_ID.with(|cell| *cell.borrow_mut() = Some(context_id));
+    OTEL_REQUEST_GUARD.with(|slot| {
+        *slot.borrow_mut() = None;
+    });
+    attach_root_span(post_response_ctx, false);
+}
+
+/// Record the HTTP response (status code, body size, captured headers, and any error) on a root span.
+fn record_http_response(span: &SpanRef) {
+    let response_code = get_response_status_code();
+    span.set_attribute(KeyValue::new(SemConv::trace::HTTP_RESPONSE_STATUS_CODE, response_code as i64));
+    span.set_attribute(KeyValue::new(SemConv::trace::HTTP_RESPONSE_BODY_SIZE, sapi::body_size() as i64));
+    let header_capture = HeaderCapture::from_config(Side::Server);
+    if !header_capture.response.is_empty() {
+        let headers = sapi::response_headers();
+        for attribute in header_capture.response_attributes_from_lines(headers.iter().map(String::as_str)) {
+            span.set_attribute(attribute);
+        }
+    }
+    if response_code >= 500 {
+        let mut func = ZVal::from("error_get_last");
+        let mut args: Vec<ZVal> = Vec::new();
+        let error = ZVal::call(&mut func, &mut args).ok();
+        if let Some(error) = error {
+            if error.get_type_info().is_array() {
+                tracing::debug!("RSHUTDOWN::HTTP error detected: {:?}", error);
+                let attributes = php_error_to_attributes(&error);
+                span.add_event("exception", attributes);
+            }
+        }
+        // https://opentelemetry.io/docs/specs/semconv/http/http-spans/#status
+        span.set_status(opentelemetry::trace::Status::error(""));
+    }
+}
+
+/// Record the exit code on the CLI root span, if there is one.
+fn record_cli_exit() {
+    let Some(context_id) = OTEL_CONTEXT_ID.with(|cell| *cell.borrow()) else {
+        return;
+    };
+    if let Some(ctx) = storage::get_context_instance(Some(context_id)) {
+        cli::record_exit(&ctx.span());
+    }
+}
+
+/// End the root span, if it exists, and clear context storage.
+fn end_root_span(is_http_request: bool) {
     let context_id = OTEL_CONTEXT_ID.with(|cell| cell.borrow_mut().take());
+    // after fastcgi_finish_request, this is the post-response span, and the response was recorded
+    let finished_context_id = FINISHED_CONTEXT_ID.with(|cell| cell.borrow_mut().take());
     let is_tracing = context_id.is_some();
     if is_tracing {
         let context_id = context_id.unwrap();
-        let is_http_request = get_sapi_module_name() != "cli";
         tracing::debug!("RSHUTDOWN::auto-closing root span...");
         let ctx = storage::get_context_instance(Some(context_id));
         if ctx.is_none() {
             tracing::warn!("RSHUTDOWN::no context found for id {}", context_id);
+            storage::clear_context_storage();
             return;
         }
         let ctx = ctx.unwrap();
         let span = ctx.span();
         if span.span_context().is_valid() {
-            if is_http_request {
-                let response_code = get_response_status_code();
-                span.set_attribute(KeyValue::new(SemConv::trace::HTTP_RESPONSE_STATUS_CODE, response_code as i64));
-                if response_code >= 500 {
-                    let mut func = ZVal::from("error_get_last");
-                    let mut args: Vec<ZVal> = Vec::new();
-                    let error = ZVal::call(&mut func, &mut args).ok();
-                    if let Some(error) = error {
-                        if error.get_type_info().is_array() {
-                            tracing::debug!("RSHUTDOWN::HTTP error detected: {:?}", error);
-                            let attributes = php_error_to_attributes(&error);
-                            span.add_event("exception", attributes);
-                        }
-                    }
-                    // https://opentelemetry.io/docs/specs/semconv/http/http-spans/#status
-                    span.set_status(opentelemetry::trace::Status::error(""));
-                }
+            if is_http_request && finished_context_id.is_none() {
+                record_http_response(&span);
             }
             span.end();
             tracing::debug!("RSHUTDOWN::removing context: {}", context_id);
             drop(ctx);
             storage::maybe_remove_context_instance(Some(context_id));
+            storage::maybe_remove_context_instance(finished_context_id);
         }
 
         OTEL_REQUEST_GUARD.with(|slot| {
@@ -491,6 +715,13 @@ fn get_server_var(key: &str) -> Option<String> {
         .and_then(|zv| z_val_to_string(zv))
 }
 
+fn get_server_float(key: &str) -> Option<f64> {
+    get_request_server()
+        .ok()
+        .and_then(|server| server.get(key))
+        .and_then(|zv| zv.as_double())
+}
+
 fn extract_request_headers(server: &ZArr) -> HashMap<String, String> {
     let mut headers = HashMap::new();
 
@@ -526,7 +757,7 @@ fn get_response_status_code() -> i32 {
 }
 
 // Parse a comma-separated key=value string into a HashMap
-fn parse_resource_attributes(s: &str) -> HashMap<String, String> {
+pub fn parse_resource_attributes(s: &str) -> HashMap<String, String> {
     s.split(',')
         .filter_map(|pair| {
             let mut parts = pair.splitn(2, '=');
@@ -539,6 +770,9 @@ fn parse_resource_attributes(s: &str) -> HashMap<String, String> {
 }
 
 fn get_propagated_context() -> Context {
+    if get_sapi_module_name() == "cli" {
+        return cli::propagated_context();
+    }
     let server = match get_request_server() {
         Ok(server) => server,
         Err(_) => return Context::current(),
@@ -553,34 +787,49 @@ fn get_propagated_context() -> Context {
 //per-request .env support
 fn set_request_dotenv(env: HashMap<String, String>) {
     for (k, v) in env {
-        unsafe { std::env::set_var(&k, &v) };
-        tracing::debug!("Set environment variable from .env {}={}", k, v);
+        config::env::set_var(&k, &v);
+        tracing::debug!("Set environment variable from .env {}={}", k, loggable_value(&k, &v));
+    }
+}
+
+/// A variable's value for logging: exporter headers (which usually carry credentials) are redacted.
+fn loggable_value<'a>(key: &str, value: &'a str) -> &'a str {
+    if key.contains("HEADERS") {
+        "<redacted>"
+    } else {
+        value
     }
 }
 
 fn backup_env() {
     tracing::debug!("Backing up OTEL_* environment variables");
-    let env = env::vars()
+    let env = std::env::vars()
         .filter(|(k, _)| k.starts_with("OTEL_"))
         .collect::<HashMap<_, _>>();
-    let pid = std::process::id();
-    ENV_BACKUP.lock().unwrap().insert(pid, env);
+    ENV_BACKUP.with(|cell| *cell.borrow_mut() = Some(env));
 }
 
+/// Restore environment variables to their state before the request.
 fn restore_env() {
+    config::env::clear();
+    let Some(backup) = ENV_BACKUP.with(|cell| cell.borrow_mut().take()) else {
+        return;
+    };
     tracing::debug!("Restoring environment variables from backup");
-    let pid = std::process::id();
-    if let Some(backup) = ENV_BACKUP.lock().unwrap().remove(&pid) {
-        // Remove any new env vars not in the backup
-        for (k, _) in env::vars() {
-            if !backup.contains_key(&k) && k.starts_with("OTEL_") {
-                tracing::debug!("Removing added environment variable {}", k);
-                unsafe { env::remove_var(&k) };
-            }
-        }
-        // Restore all backed up vars
-        for (k, v) in backup {
-            unsafe { env::set_var(k, v) };
+    // With ZTS, request-scoped variables are never written to the process environment (which is
+    // shared by all threads), so there is nothing to restore
+    if cfg!(otel_zts) {
+        return;
+    }
+    // Remove any new env vars not in the backup
+    for (k, _) in std::env::vars() {
+        if !backup.contains_key(&k) && k.starts_with("OTEL_") {
+            tracing::debug!("Removing added environment variable {}", k);
+            unsafe { std::env::remove_var(&k) };
         }
     }
+    // Restore all backed up vars
+    for (k, v) in backup {
+        unsafe { std::env::set_var(k, v) };
+    }
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+// CLI root span (otel.cli.create_root_span): named after the script, or after the command of a
+// Symfony (bin/console) or Laminas (vendor/bin/laminas) console application, with process
+// attributes. The exit code, including exit() codes and fatal errors, is recorded by RSHUTDOWN. A
+// parent context is extracted from the TRACEPARENT, TRACESTATE and BAGGAGE environment variables.
+use opentelemetry::{
+    Array, Context, KeyValue, StringValue, Value, global,
+    trace::{SpanRef, Status},
+};
+use opentelemetry_semantic_conventions as SemConv;
+use phper::{eg, pg};
+use std::{collections::HashMap, ffi::CStr, path::Path};
+
+const DEFAULT_SPAN_NAME: &str = "php:cli";
+const CONSOLE_SCRIPTS: &[&str] = &["console", "laminas"];
+
+/// Span name for a command line: the script's file name, plus the command for console applications
+/// (eg "console cache:clear").
+fn span_name_from(argv: &[String]) -> Option<String> {
+    let script = Path::new(argv.first()?).file_name()?.to_string_lossy().into_owned();
+    if CONSOLE_SCRIPTS.contains(&script.as_str())
+        && let Some(command) = argv.iter().skip(1).find(|arg| !arg.starts_with('-'))
+    {
+        return Some(format!("{} {}", script, command));
+    }
+    Some(script)
+}
+
+/// $_SERVER['argv']
+fn argv() -> Vec<String> {
+    super::get_request_server()
+        .ok()
+        .and_then(|server| server.get("argv"))
+        .and_then(|argv| argv.as_z_arr())
+        .map(|argv| {
+            argv.iter()
+                .filter_map(|(_, arg)| arg.as_z_str().and_then(|arg| arg.to_str().ok()).map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn php_binary() -> Option<String> {
+    let php_binary = unsafe { pg!(php_binary) };
+    if php_binary.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(php_binary) }.to_string_lossy().into_owned())
+}
+
+pub fn root_span_name() -> String {
+    span_name_from(&argv()).unwrap_or_else(|| DEFAULT_SPAN_NAME.to_string())
+}
+
+/// process.executable.path and process.command_args (the PHP binary, then the script's argv).
+pub fn attributes() -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    let php_binary = php_binary();
+    if let Some(php_binary) = &php_binary {
+        attributes.push(KeyValue::new(SemConv::attribute::PROCESS_EXECUTABLE_PATH, php_binary.clone()));
+    }
+    let command_args: Vec<StringValue> = php_binary.into_iter().chain(argv()).map(StringValue::from).collect();
+    if !command_args.is_empty() {
+        attributes.push(KeyValue::new(SemConv::attribute::PROCESS_COMMAND_ARGS, Value::Array(Array::String(command_args))));
+    }
+    attributes
+}
+
+/// Parent context from the environment, as set by a parent process.
+pub fn propagated_context() -> Context {
+    let names = [("traceparent", "TRACEPARENT"), ("tracestate", "TRACESTATE"), ("baggage", "BAGGAGE")];
+    let carrier: HashMap<String, String> = names
+        .into_iter()
+        .filter_map(|(key, name)| std::env::var(name).ok().map(|value| (key.to_string(), value)))
+        .collect();
+    global::get_text_map_propagator(|prop| prop.extract(&carrier))
+}
+
+/// Record the exit code on the root span, with an error status if non-zero.
+pub fn record_exit(span: &SpanRef) {
+    let exit_code = unsafe { eg!(exit_status) } as i64;
+    span.set_attribute(KeyValue::new(SemConv::attribute::PROCESS_EXIT_CODE, exit_code));
+    if exit_code != 0 {
+        span.set_status(Status::error(format!("exit code {}", exit_code)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_span_name() {
+        assert_eq!(span_name_from(&args(&["/app/cron/cleanup.php", "--days=7"])).as_deref(), Some("cleanup.php"));
+        assert_eq!(span_name_from(&args(&["bin/console", "-vv", "cache:clear", "--env=prod"])).as_deref(), Some("console cache:clear"));
+        assert_eq!(span_name_from(&args(&["vendor/bin/laminas", "app:import"])).as_deref(), Some("laminas app:import"));
+        assert_eq!(span_name_from(&args(&["bin/console"])).as_deref(), Some("console"));
+        assert_eq!(span_name_from(&[]), None);
+    }
+}
@@ -0,0 +1,149 @@
+// Request exclusion rules, evaluated before the root span is created, in addition to the wildcard
+// patterns of OTEL_PHP_EXCLUDED_URLS:
+// - OTEL_PHP_EXCLUDED_URL_PATTERN: regex, matched against the request URI (including query string)
+// - OTEL_PHP_EXCLUDED_METHODS: comma-separated HTTP methods, eg OPTIONS,HEAD
+// - OTEL_PHP_EXCLUDED_USER_AGENTS: regex, matched case-insensitively against the User-Agent
+// - OTEL_PHP_EXCLUDED_CLIENT_IPS: comma-separated IP addresses or CIDR ranges, matched against the
+//   client address (see OTEL_PHP_TRUSTED_PROXIES)
+// Like other OTEL_* variables, these can be set per application in a .env file.
+use super::{
+    http::{self, IpRange},
+    parse_cache::ParseCache,
+};
+use crate::config;
+use regex::{Regex, RegexBuilder};
+
+const MAX_CACHED_PATTERNS: usize = 64;
+
+/// Compiled patterns, keyed by pattern. Invalid patterns are cached as None, so that they are only
+/// reported once.
+static PATTERNS: ParseCache<Option<Regex>> = ParseCache::new(MAX_CACHED_PATTERNS);
+
+#[derive(Debug, Default)]
+struct Rules {
+    url_pattern: Option<Regex>,
+    methods: Vec<String>,
+    user_agents: Option<Regex>,
+    client_ips: Vec<IpRange>,
+}
+
+#[derive(Debug, Default)]
+struct Request {
+    uri: Option<String>,
+    method: Option<String>,
+    user_agent: Option<String>,
+    client_address: Option<String>,
+}
+
+fn compile(pattern: &str, case_insensitive: bool) -> Option<Regex> {
+    let key = format!("{}{}", if case_insensitive { "i:" } else { "s:" }, pattern);
+    PATTERNS.get_or_parse(&key, || match RegexBuilder::new(pattern).case_insensitive(case_insensitive).build() {
+        Ok(regex) => Some(regex),
+        Err(err) => {
+            tracing::warn!("Ignoring invalid exclusion pattern {}: {}", pattern, err);
+            None
+        }
+    })
+}
+
+impl Rules {
+    fn from_config() -> Self {
+        let pattern = |name: &str, case_insensitive: bool| {
+            config::env::var(name)
+                .ok()
+                .filter(|pattern| !pattern.trim().is_empty())
+                .and_then(|pattern| compile(pattern.trim(), case_insensitive))
+        };
+        Self {
+            url_pattern: pattern("OTEL_PHP_EXCLUDED_URL_PATTERN", false),
+            methods: config::env::var("OTEL_PHP_EXCLUDED_METHODS")
+                .map(|list| {
+                    list.split(',')
+                        .map(|method| method.trim().to_ascii_uppercase())
+                        .filter(|method| !method.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            user_agents: pattern("OTEL_PHP_EXCLUDED_USER_AGENTS", true),
+            client_ips: config::env::var("OTEL_PHP_EXCLUDED_CLIENT_IPS")
+                .map(|list| http::parse_ip_ranges(&list))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.url_pattern.is_none() && self.methods.is_empty() && self.user_agents.is_none() && self.client_ips.is_empty()
+    }
+
+    /// The name of the first rule which excludes the request, if any.
+    fn matches(&self, request: &Request) -> Option<&'static str> {
+        if let (Some(pattern), Some(uri)) = (&self.url_pattern, &request.uri)
+            && pattern.is_match(uri)
+        {
+            return Some("url pattern");
+        }
+        if let Some(method) = &request.method
+            && self.methods.iter().any(|excluded| excluded.eq_ignore_ascii_case(method))
+        {
+            return Some("method");
+        }
+        if let (Some(pattern), Some(user_agent)) = (&self.user_agents, &request.user_agent)
+            && pattern.is_match(user_agent)
+        {
+            return Some("user agent");
+        }
+        if let Some(ip) = request.client_address.as_deref().and_then(http::parse_ip)
+            && self.client_ips.iter().any(|range| range.contains(ip))
+        {
+            return Some("client ip");
+        }
+        None
+    }
+}
+
+/// Check the current request against the exclusion rules, returning the name of the matching rule.
+pub fn excluded_by(uri: Option<&str>, method: Option<&str>) -> Option<&'static str> {
+    let rules = Rules::from_config();
+    if rules.is_empty() {
+        return None;
+    }
+    let request = Request {
+        uri: uri.map(str::to_string),
+        method: method.map(str::to_string),
+        user_agent: super::get_server_var("HTTP_USER_AGENT"),
+        client_address: if rules.client_ips.is_empty() { None } else { http::request_client_address() },
+    };
+    rules.matches(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, method: &str, user_agent: &str, client_address: &str) -> Request {
+        Request {
+            uri: Some(uri.to_string()),
+            method: Some(method.to_string()),
+            user_agent: Some(user_agent.to_string()),
+            client_address: Some(client_address.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let rules = Rules {
+            url_pattern: compile(r"^/(healthz|status)(\?.*)?$", false),
+            methods: vec!["OPTIONS".to_string()],
+            user_agents: compile("ELB-HealthChecker|uptimerobot", true),
+            client_ips: http::parse_ip_ranges("10.0.0.0/8"),
+        };
+        let browser = "Mozilla/5.0";
+        assert_eq!(rules.matches(&request("/healthz?full=1", "GET", browser, "1.2.3.4")), Some("url pattern"));
+        assert_eq!(rules.matches(&request("/api", "options", browser, "1.2.3.4")), Some("method"));
+        assert_eq!(rules.matches(&request("/api", "GET", "UptimeRobot/2.0", "1.2.3.4")), Some("user agent"));
+        assert_eq!(rules.matches(&request("/api", "GET", browser, "10.1.2.3")), Some("client ip"));
+        assert_eq!(rules.matches(&request("/healthz/deep", "GET", browser, "1.2.3.4")), None);
+        assert_eq!(rules.matches(&Request::default()), None);
+        assert!(compile("(unclosed", false).is_none());
+    }
+}
@@ -0,0 +1,470 @@
+// HTTP server semantic convention attributes for the root span, from $_SERVER. client.address
+// honours X-Forwarded-For (and url.scheme X-Forwarded-Proto) only when the connection comes from
+// a trusted proxy, listed in OTEL_PHP_TRUSTED_PROXIES as IP addresses or CIDR ranges.
+// Response headers exposing the trace (traceresponse, Server-Timing) are opt-in, via
+// OTEL_PHP_TRACE_RESPONSE_HEADERS. The root span starts at REQUEST_TIME_FLOAT, and the time the
+// request spent queued before that (from an X-Request-Start or X-Queue-Start header added by a
+// load balancer or web server) is recorded as http.server.request.queue_time, in seconds.
+use crate::{
+    config::{self, http_headers::HeaderCapture},
+    sapi::PendingHeader,
+};
+use opentelemetry::{KeyValue, trace::SpanContext};
+use opentelemetry_semantic_conventions as SemConv;
+use std::{
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// An IP address range in CIDR notation (a single address is a /32 or /128).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (address, prefix) = match s.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().ok()?)),
+            None => (s.trim(), None),
+        };
+        let network = address.parse::<IpAddr>().ok()?.to_canonical();
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max_prefix);
+        if prefix > max_prefix {
+            return None;
+        }
+        Some(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse a comma-separated list of IP ranges, logging (and skipping) any which are invalid.
+pub fn parse_ip_ranges(list: &str) -> Vec<IpRange> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| {
+            let range = IpRange::parse(s);
+            if range.is_none() {
+                tracing::warn!("Ignoring invalid IP range {}", s);
+            }
+            range
+        })
+        .collect()
+}
+
+/// Parse an address as found in REMOTE_ADDR or X-Forwarded-For, which may include a port
+/// (1.2.3.4:5678 or [::1]:5678).
+pub fn parse_ip(s: &str) -> Option<IpAddr> {
+    let s = s.trim();
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+    let host = match s.strip_prefix('[') {
+        Some(rest) => rest.split_once(']')?.0,
+        None => s.rsplit_once(':')?.0,
+    };
+    host.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
+
+fn trusted_proxies() -> Vec<IpRange> {
+    config::env::var("OTEL_PHP_TRUSTED_PROXIES")
+        .map(|list| parse_ip_ranges(&list))
+        .unwrap_or_default()
+}
+
+fn is_trusted(address: &str, trusted: &[IpRange]) -> bool {
+    parse_ip(address).is_some_and(|ip| trusted.iter().any(|range| range.contains(ip)))
+}
+
+/// The client's address: the peer, unless it is a trusted proxy, in which case X-Forwarded-For is
+/// read right to left, and the first address which is not a trusted proxy is the client.
+fn client_address(remote_addr: Option<String>, forwarded_for: Option<String>, trusted: &[IpRange]) -> Option<String> {
+    let remote_addr = remote_addr?;
+    if !is_trusted(&remote_addr, trusted) {
+        return Some(remote_addr);
+    }
+    let Some(forwarded_for) = forwarded_for else {
+        return Some(remote_addr);
+    };
+    let hops: Vec<&str> = forwarded_for.split(',').map(str::trim).filter(|hop| !hop.is_empty()).collect();
+    let client = hops
+        .iter()
+        .rev()
+        .find(|hop| !is_trusted(hop, trusted))
+        .or(hops.first());
+    match client {
+        Some(client) => Some(parse_ip(client).map(|ip| ip.to_string()).unwrap_or_else(|| client.to_string())),
+        None => Some(remote_addr),
+    }
+}
+
+/// Requests which started longer ago than this have an implausible REQUEST_TIME_FLOAT or queue
+/// start header.
+const MAX_REQUEST_AGE: Duration = Duration::from_secs(3600);
+
+/// Request start time, from REQUEST_TIME_FLOAT (seconds since the epoch), if plausible.
+pub fn request_start_time(request_time: f64, now: SystemTime) -> Option<SystemTime> {
+    if !request_time.is_finite() || request_time <= 0.0 {
+        return None;
+    }
+    let start = UNIX_EPOCH + Duration::from_secs_f64(request_time);
+    match now.duration_since(start) {
+        Ok(age) if age <= MAX_REQUEST_AGE => Some(start),
+        Ok(_) => None,
+        // allow for clock skew between PHP's and our reading of the clock
+        Err(err) if err.duration() < Duration::from_secs(1) => Some(now),
+        Err(_) => None,
+    }
+}
+
+/// Parse an X-Request-Start/X-Queue-Start value, "t=<timestamp>" or "<timestamp>", with the
+/// timestamp in seconds (possibly fractional), milliseconds, microseconds or nanoseconds since the
+/// epoch, into seconds.
+fn parse_request_start(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = value.strip_prefix("t=").unwrap_or(value);
+    let mut timestamp = value.trim().parse::<f64>().ok().filter(|timestamp| timestamp.is_finite() && *timestamp > 0.0)?;
+    while timestamp > 1e11 {
+        timestamp /= 1000.0;
+    }
+    Some(timestamp)
+}
+
+/// Seconds between the queue start header value and the request start.
+fn queue_time_from(header: &str, request_time: f64) -> Option<f64> {
+    let queue_start = parse_request_start(header)?;
+    let queue_time = request_time - queue_start;
+    (queue_time >= 0.0 && queue_time <= MAX_REQUEST_AGE.as_secs_f64()).then_some(queue_time)
+}
+
+/// http.server.request.queue_time attribute for the current request, given its REQUEST_TIME_FLOAT.
+pub fn queue_time_attribute(request_time: f64) -> Option<KeyValue> {
+    let header = super::get_server_var("HTTP_X_REQUEST_START").or_else(|| super::get_server_var("HTTP_X_QUEUE_START"))?;
+    let queue_time = queue_time_from(&header, request_time)?;
+    Some(KeyValue::new("http.server.request.queue_time", queue_time))
+}
+
+/// Split a Host header into address and port, allowing for IPv6 literals ([::1]:8080).
+fn split_host_port(host: &str) -> (&str, Option<u16>) {
+    if let Some(rest) = host.strip_prefix('[') {
+        return match rest.split_once(']') {
+            Some((address, port)) => (address, port.strip_prefix(':').and_then(|port| port.parse().ok())),
+            None => (host, None),
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((address, port)) if !address.contains(':') => (address, port.parse().ok()),
+        _ => (host, None),
+    }
+}
+
+/// HTTP/1.1 -> 1.1, HTTP/2.0 -> 2
+fn protocol_version(server_protocol: &str) -> Option<String> {
+    let version = server_protocol.strip_prefix("HTTP/")?;
+    Some(match version {
+        "2.0" => "2".to_string(),
+        "3.0" => "3".to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn scheme(server: &impl Fn(&str) -> Option<String>, trusted_peer: bool) -> String {
+    if trusted_peer
+        && let Some(proto) = server("HTTP_X_FORWARDED_PROTO")
+        && let Some(proto) = proto.split(',').next().map(|proto| proto.trim().to_ascii_lowercase())
+        && !proto.is_empty()
+    {
+        return proto;
+    }
+    if server("HTTPS").is_some_and(|https| !https.is_empty() && !https.eq_ignore_ascii_case("off")) {
+        return "https".to_string();
+    }
+    server("REQUEST_SCHEME")
+        .filter(|scheme| !scheme.is_empty())
+        .unwrap_or_else(|| "http".to_string())
+}
+
+/// Attributes for an HTTP server root span, from $_SERVER variables (via `server`).
+fn attributes_from(server: impl Fn(&str) -> Option<String>, body_length: u64, trusted: &[IpRange]) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    let trusted_peer = server("REMOTE_ADDR").is_some_and(|remote_addr| is_trusted(&remote_addr, trusted));
+
+    attributes.push(KeyValue::new(SemConv::trace::URL_SCHEME, scheme(&server, trusted_peer)));
+    if let Some(uri) = server("REQUEST_URI") {
+        let (path, query) = match uri.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (uri, None),
+        };
+        attributes.push(KeyValue::new(SemConv::trace::URL_PATH, path));
+        if let Some(query) = query.filter(|query| !query.is_empty()) {
+            attributes.push(KeyValue::new(SemConv::trace::URL_QUERY, query));
+        }
+    }
+    let host = server("HTTP_HOST").filter(|host| !host.is_empty());
+    let (address, port) = match &host {
+        Some(host) => {
+            let (address, port) = split_host_port(host);
+            (Some(address.to_string()), port)
+        }
+        None => (server("SERVER_NAME").filter(|name| !name.is_empty()), None),
+    };
+    if let Some(address) = address {
+        attributes.push(KeyValue::new(SemConv::trace::SERVER_ADDRESS, address));
+    }
+    let port = port.or_else(|| server("SERVER_PORT").and_then(|port| port.trim().parse::<u16>().ok()));
+    if let Some(port) = port {
+        attributes.push(KeyValue::new(SemConv::trace::SERVER_PORT, port as i64));
+    }
+    if let Some(client) = client_address(server("REMOTE_ADDR"), server("HTTP_X_FORWARDED_FOR"), trusted) {
+        attributes.push(KeyValue::new(SemConv::trace::CLIENT_ADDRESS, client));
+    }
+    if let Some(user_agent) = server("HTTP_USER_AGENT").filter(|user_agent| !user_agent.is_empty()) {
+        attributes.push(KeyValue::new(SemConv::trace::USER_AGENT_ORIGINAL, user_agent));
+    }
+    if let Some(version) = server("SERVER_PROTOCOL").and_then(|protocol| protocol_version(&protocol)) {
+        attributes.push(KeyValue::new(SemConv::trace::NETWORK_PROTOCOL_VERSION, version));
+    }
+    if body_length > 0 {
+        attributes.push(KeyValue::new(SemConv::trace::HTTP_REQUEST_BODY_SIZE, body_length as i64));
+    }
+    attributes
+}
+
+/// $_SERVER key of a request header (X-Request-Id -> HTTP_X_REQUEST_ID)
+fn server_key(header: &str) -> String {
+    let key = header.to_ascii_uppercase().replace('-', "_");
+    match key.as_str() {
+        "CONTENT_TYPE" | "CONTENT_LENGTH" => key,
+        _ => format!("HTTP_{}", key),
+    }
+}
+
+fn header_attributes_from(server: impl Fn(&str) -> Option<String>, capture: &HeaderCapture) -> Vec<KeyValue> {
+    capture.request
+        .iter()
+        .filter_map(|name| server(&server_key(name)).map(|value| capture.request_attribute(name, vec![value])))
+        .collect()
+}
+
+/// http.request.header.<name> attributes for the captured headers of the current HTTP request.
+pub fn request_header_attributes(capture: &HeaderCapture) -> Vec<KeyValue> {
+    header_attributes_from(super::get_server_var, capture)
+}
+
+/// The client address of the current HTTP request (see client_address).
+pub fn request_client_address() -> Option<String> {
+    client_address(super::get_server_var("REMOTE_ADDR"), super::get_server_var("HTTP_X_FORWARDED_FOR"), &trusted_proxies())
+}
+
+/// Attributes for the root span of the current HTTP request.
+pub fn request_attributes(body_length: u64) -> Vec<KeyValue> {
+    attributes_from(super::get_server_var, body_length, &trusted_proxies())
+}
+
+/// W3C traceparent value for a span context (version 00).
+pub fn traceparent(span_context: &SpanContext) -> String {
+    format!(
+        "00-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags().to_u8()
+    )
+}
+
+/// W3C traceresponse (https://www.w3.org/TR/trace-context-2/#traceresponse-header) and
+/// Server-Timing headers for a root span, as configured by OTEL_PHP_TRACE_RESPONSE_HEADERS (a
+/// comma-separated list of traceresponse, server-timing).
+fn trace_response_headers_from(list: &str, span_context: &SpanContext) -> Vec<PendingHeader> {
+    if !span_context.is_valid() {
+        return Vec::new();
+    }
+    let value = traceparent(span_context);
+    let mut headers = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        match name.to_ascii_lowercase().as_str() {
+            "traceresponse" => headers.push(PendingHeader {
+                line: format!("traceresponse: {}", value),
+                replace: true,
+            }),
+            // Server-Timing may be repeated, so the application's own metrics are kept
+            "server-timing" => headers.push(PendingHeader {
+                line: format!("Server-Timing: traceparent;desc=\"{}\"", value),
+                replace: false,
+            }),
+            other => tracing::warn!("Unsupported trace response header {}, ignoring", other),
+        }
+    }
+    headers
+}
+
+/// Response headers to add for the root span of the current HTTP request.
+pub fn trace_response_headers(span_context: &SpanContext) -> Vec<PendingHeader> {
+    match config::env::var("OTEL_PHP_TRACE_RESPONSE_HEADERS") {
+        Ok(list) => trace_response_headers_from(&list, span_context),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn server(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    fn get<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a opentelemetry::Value> {
+        attributes.iter().find(|kv| kv.key.as_str() == key).map(|kv| &kv.value)
+    }
+
+    #[test]
+    fn test_ip_range() {
+        let range = IpRange::parse("10.0.0.0/8").unwrap();
+        assert!(range.contains("10.1.2.3".parse().unwrap()));
+        assert!(range.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        let single = IpRange::parse("fd00::1").unwrap();
+        assert!(single.contains("fd00::1".parse().unwrap()));
+        assert!(!single.contains("fd00::2".parse().unwrap()));
+        assert!(IpRange::parse("0.0.0.0/0").unwrap().contains("8.8.8.8".parse().unwrap()));
+        assert_eq!(IpRange::parse("10.0.0.0/33"), None);
+        assert_eq!(IpRange::parse("nonsense"), None);
+    }
+
+    #[test]
+    fn test_client_address() {
+        let trusted = parse_ip_ranges("10.0.0.0/8, 192.168.1.1");
+        let resolve = |remote: &str, forwarded: Option<&str>| {
+            client_address(Some(remote.to_string()), forwarded.map(str::to_string), &trusted)
+        };
+        // untrusted peer: X-Forwarded-For is ignored
+        assert_eq!(resolve("1.2.3.4", Some("5.6.7.8")).as_deref(), Some("1.2.3.4"));
+        assert_eq!(resolve("10.0.0.1", None).as_deref(), Some("10.0.0.1"));
+        assert_eq!(resolve("10.0.0.1", Some("5.6.7.8")).as_deref(), Some("5.6.7.8"));
+        // spoofed leftmost entries are skipped
+        assert_eq!(resolve("10.0.0.1", Some("6.6.6.6, 5.6.7.8, 192.168.1.1")).as_deref(), Some("5.6.7.8"));
+        assert_eq!(resolve("10.0.0.1", Some("10.0.0.2, 10.0.0.3")).as_deref(), Some("10.0.0.2"));
+        assert_eq!(resolve("10.0.0.1", Some("[2001:db8::1]:1234")).as_deref(), Some("2001:db8::1"));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let attributes = attributes_from(
+            server(&[
+                ("REQUEST_URI", "/users/1?page=2"),
+                ("HTTP_HOST", "example.com:8443"),
+                ("HTTPS", "on"),
+                ("REMOTE_ADDR", "1.2.3.4"),
+                ("HTTP_USER_AGENT", "curl/8.0"),
+                ("SERVER_PROTOCOL", "HTTP/2.0"),
+            ]),
+            42,
+            &[],
+        );
+        assert_eq!(get(&attributes, "url.scheme").map(|v| v.as_str()).as_deref(), Some("https"));
+        assert_eq!(get(&attributes, "url.path").map(|v| v.as_str()).as_deref(), Some("/users/1"));
+        assert_eq!(get(&attributes, "url.query").map(|v| v.as_str()).as_deref(), Some("page=2"));
+        assert_eq!(get(&attributes, "server.address").map(|v| v.as_str()).as_deref(), Some("example.com"));
+        assert_eq!(get(&attributes, "server.port"), Some(&opentelemetry::Value::I64(8443)));
+        assert_eq!(get(&attributes, "client.address").map(|v| v.as_str()).as_deref(), Some("1.2.3.4"));
+        assert_eq!(get(&attributes, "user_agent.original").map(|v| v.as_str()).as_deref(), Some("curl/8.0"));
+        assert_eq!(get(&attributes, "network.protocol.version").map(|v| v.as_str()).as_deref(), Some("2"));
+        assert_eq!(get(&attributes, "http.request.body.size"), Some(&opentelemetry::Value::I64(42)));
+    }
+
+    #[test]
+    fn test_header_attributes() {
+        let capture = HeaderCapture::new(
+            vec!["x-request-id".to_string(), "content-type".to_string(), "x-missing".to_string()],
+            vec![],
+            vec![],
+        );
+        let attributes = header_attributes_from(
+            server(&[("HTTP_X_REQUEST_ID", "abc"), ("CONTENT_TYPE", "application/json")]),
+            &capture,
+        );
+        let keys: Vec<&str> = attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(keys, vec!["http.request.header.x-request-id", "http.request.header.content-type"]);
+    }
+
+    #[test]
+    fn test_attributes_forwarded_proto() {
+        let trusted = parse_ip_ranges("10.0.0.0/8");
+        let vars = [
+            ("REQUEST_URI", "/"),
+            ("SERVER_NAME", "[::1]"),
+            ("SERVER_PORT", "80"),
+            ("REMOTE_ADDR", "10.0.0.1"),
+            ("HTTP_X_FORWARDED_PROTO", "HTTPS"),
+        ];
+        let attributes = attributes_from(server(&vars), 0, &trusted);
+        assert_eq!(get(&attributes, "url.scheme").map(|v| v.as_str()).as_deref(), Some("https"));
+        assert_eq!(get(&attributes, "server.port"), Some(&opentelemetry::Value::I64(80)));
+        assert!(get(&attributes, "url.query").is_none());
+        assert!(get(&attributes, "http.request.body.size").is_none());
+        // not from a trusted proxy
+        let attributes = attributes_from(server(&vars), 0, &[]);
+        assert_eq!(get(&attributes, "url.scheme").map(|v| v.as_str()).as_deref(), Some("http"));
+    }
+
+    #[test]
+    fn test_trace_response_headers() {
+        use opentelemetry::trace::{SpanId, TraceFlags, TraceId, TraceState};
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let headers = trace_response_headers_from("traceresponse, Server-Timing, other", &span_context);
+        let lines: Vec<(&str, bool)> = headers.iter().map(|header| (header.line.as_str(), header.replace)).collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("traceresponse: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", true),
+                ("Server-Timing: traceparent;desc=\"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\"", false),
+            ]
+        );
+        assert!(trace_response_headers_from("traceresponse", &SpanContext::empty_context()).is_empty());
+    }
+
+    #[test]
+    fn test_queue_time() {
+        let request_time = 1_700_000_000.5;
+        for header in ["t=1700000000.25", "t=1700000000250", "1700000000250000", "t=1700000000250000000"] {
+            let queue_time = queue_time_from(header, request_time).unwrap();
+            assert!((queue_time - 0.25).abs() < 1e-6, "{} gave {}", header, queue_time);
+        }
+        assert_eq!(queue_time_from("t=1700000001", request_time), None);
+        assert_eq!(queue_time_from("t=abc", request_time), None);
+        assert_eq!(queue_time_from("", request_time), None);
+    }
+
+    #[test]
+    fn test_request_start_time() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(request_start_time(1_699_999_999.5, now), Some(UNIX_EPOCH + Duration::from_secs_f64(1_699_999_999.5)));
+        assert_eq!(request_start_time(1_700_000_000.2, now), Some(now));
+        assert_eq!(request_start_time(1_600_000_000.0, now), None);
+        assert_eq!(request_start_time(0.0, now), None);
+    }
+}
@@ -0,0 +1,53 @@
+// Cache of values parsed from configuration strings (route rules, exclusion patterns), which may
+// differ per .env, so that they are not parsed on every request. The cache is bounded by clearing
+// it when full, and stays usable if a panic poisoned its lock.
+use std::{collections::HashMap, sync::Mutex};
+
+pub struct ParseCache<V> {
+    max_entries: usize,
+    entries: Mutex<Option<HashMap<String, V>>>,
+}
+
+impl<V: Clone> ParseCache<V> {
+    pub const fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            entries: Mutex::new(None),
+        }
+    }
+
+    /// The cached value for a key, or else the value from `parse`, which is cached.
+    pub fn get_or_parse<F: FnOnce() -> V>(&self, key: &str, parse: F) -> V {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let entries = entries.get_or_insert_with(HashMap::new);
+        if let Some(value) = entries.get(key) {
+            return value.clone();
+        }
+        if entries.len() >= self.max_entries {
+            entries.clear();
+        }
+        let value = parse();
+        entries.insert(key.to_string(), value.clone());
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleared_when_full() {
+        let cache = ParseCache::new(2);
+        assert_eq!(cache.get_or_parse("a", || 1), 1);
+        assert_eq!(cache.get_or_parse("a", || 2), 1);
+        assert_eq!(cache.get_or_parse("b", || 3), 3);
+        // full, so "a" is dropped along with "b"
+        assert_eq!(cache.get_or_parse("c", || 4), 4);
+        assert_eq!(cache.get_or_parse("a", || 5), 5);
+        assert_eq!(cache.get_or_parse("c", || 6), 4);
+    }
+}
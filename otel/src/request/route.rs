@@ -0,0 +1,97 @@
+// Route-based root span naming, for applications without a framework plugin. Rules map a URL path
+// regex to a route template (`^/invoice/\d+$ => /invoice/{id}`; `$1` or `${name}` expand capture
+// groups), separated by `;` or newlines. They are read from OTEL_PHP_HTTP_ROUTES (eg in .env), or
+// otel.http.routes, and the first matching rule wins. Optionally, SCRIPT_NAME is used as the route
+// when no rule matches, for apps with one script per endpoint.
+use super::parse_cache::ParseCache;
+use crate::config;
+use phper::ini::ini_get;
+use regex::Regex;
+use std::{ffi::CStr, sync::Arc};
+
+/// Compiled rules are cached per distinct rules string (which may differ per .env).
+const MAX_CACHED_RULE_SETS: usize = 64;
+
+static RULE_SETS: ParseCache<Arc<Vec<Rule>>> = ParseCache::new(MAX_CACHED_RULE_SETS);
+
+#[derive(Debug)]
+struct Rule {
+    pattern: Regex,
+    template: String,
+}
+
+fn parse_rules(rules: &str) -> Vec<Rule> {
+    rules
+        .split([';', '\n'])
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .filter_map(|rule| {
+            let Some((pattern, template)) = rule.split_once("=>") else {
+                tracing::warn!("Ignoring route rule without '=>': {}", rule);
+                return None;
+            };
+            match Regex::new(pattern.trim()) {
+                Ok(pattern) => Some(Rule { pattern, template: template.trim().to_string() }),
+                Err(err) => {
+                    tracing::warn!("Ignoring route rule with invalid regex {}: {}", pattern.trim(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn get_rules(rules: &str) -> Arc<Vec<Rule>> {
+    RULE_SETS.get_or_parse(rules, || Arc::new(parse_rules(rules)))
+}
+
+fn match_route(rules: &[Rule], path: &str) -> Option<String> {
+    rules.iter().find_map(|rule| {
+        let captures = rule.pattern.captures(path)?;
+        let mut route = String::new();
+        captures.expand(&rule.template, &mut route);
+        Some(route)
+    })
+}
+
+fn script_fallback_enabled() -> bool {
+    match config::env::var("OTEL_PHP_HTTP_ROUTE_SCRIPT_FALLBACK") {
+        Ok(value) => value.trim().eq_ignore_ascii_case("true"),
+        Err(_) => ini_get::<bool>(config::ini::OTEL_HTTP_ROUTE_SCRIPT_FALLBACK),
+    }
+}
+
+/// The route (http.route) for a request path, from the configured rules, or SCRIPT_NAME if the
+/// fallback is enabled.
+pub fn resolve(path: &str, script_name: Option<String>) -> Option<String> {
+    let rules = config::env::var("OTEL_PHP_HTTP_ROUTES").ok().or_else(|| {
+        ini_get::<Option<&CStr>>(config::ini::OTEL_HTTP_ROUTES)
+            .and_then(|rules| rules.to_str().ok())
+            .map(str::to_string)
+    });
+    if let Some(rules) = rules.filter(|rules| !rules.trim().is_empty())
+        && let Some(route) = match_route(&get_rules(&rules), path)
+    {
+        return Some(route);
+    }
+    if script_fallback_enabled() {
+        return script_name.filter(|script_name| !script_name.is_empty());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_route() {
+        let rules = parse_rules(r"^/invoice/\d+$ => /invoice/{id}; ^/users/(?P<section>[a-z]+)/\d+ => /users/${section}/{id}
+            not a rule; ^/(unclosed => /never");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(match_route(&rules, "/invoice/42").as_deref(), Some("/invoice/{id}"));
+        assert_eq!(match_route(&rules, "/invoice/42/pdf"), None);
+        assert_eq!(match_route(&rules, "/users/admin/7/edit").as_deref(), Some("/users/admin/{id}"));
+        assert_eq!(match_route(&rules, "/"), None);
+    }
+}
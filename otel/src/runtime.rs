@@ -1,12 +1,24 @@
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
 use tokio::runtime::Runtime;
 
-static TOKIO_RUNTIME: OnceCell<Runtime> = OnceCell::new();
+// The runtime is leaked rather than owned by the static, so that a forked child can abandon the
+// runtime it inherited (whose worker threads only exist in the parent) without dropping it.
+static TOKIO_RUNTIME: Lazy<Mutex<Option<&'static Runtime>>> = Lazy::new(|| Mutex::new(None));
 
 pub fn init_tokio_runtime() -> &'static Runtime {
-    if TOKIO_RUNTIME.get().is_none() {
-        let runtime = Runtime::new().expect("Failed to create Tokio runtime required for gRPC export");
-        TOKIO_RUNTIME.set(runtime).expect("Tokio runtime already set");
-    }
-    TOKIO_RUNTIME.get().expect("Tokio runtime not initialized")
+    // hold the lock while creating, so that concurrent threads (ZTS) cannot race to create the runtime
+    let mut runtime = TOKIO_RUNTIME.lock().unwrap();
+    *runtime.get_or_insert_with(|| {
+        Box::leak(Box::new(Runtime::new().expect("Failed to create Tokio runtime required for gRPC export")))
+    })
 }
+
+/// In a forked child, forget the runtime inherited from the parent. A new one is created on demand.
+pub fn forget_after_fork() {
+    let mut runtime = match TOKIO_RUNTIME.lock() {
+        Ok(runtime) => runtime,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *runtime = None;
+}
\ No newline at end of file
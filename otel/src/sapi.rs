@@ -0,0 +1,127 @@
+// SAPI hooks. The SAPI's unbuffered write (through which all response output passes, after any
+// output buffers and handlers) is wrapped to count the response body bytes of each request, and
+// send_headers is wrapped to add headers at the last moment, after anything the application set
+// with header().
+use phper::{sg, sys};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::{c_char, c_int, c_void},
+};
+
+type UbWrite = unsafe extern "C" fn(str_: *const c_char, str_length: usize) -> usize;
+type SendHeaders = unsafe extern "C" fn(sapi_headers: *mut sys::sapi_headers_struct) -> c_int;
+
+static mut UPSTREAM_UB_WRITE: Option<UbWrite> = None;
+static mut UPSTREAM_SEND_HEADERS: Option<SendHeaders> = None;
+
+/// A response header to add when headers are sent: the header line, and whether it replaces any
+/// header of the same name.
+pub struct PendingHeader {
+    pub line: String,
+    pub replace: bool,
+}
+
+thread_local! {
+    static BODY_SIZE: Cell<u64> = const { Cell::new(0) };
+    static PENDING_HEADERS: RefCell<Vec<PendingHeader>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Wrap the SAPI's ub_write and send_headers. Called from MINIT, for non-CLI SAPIs.
+pub fn init() {
+    unsafe {
+        let sapi_module = &raw mut sys::sapi_module;
+        let upstream = (*sapi_module).ub_write;
+        if upstream.is_some() {
+            UPSTREAM_UB_WRITE = upstream;
+            (*sapi_module).ub_write = Some(ub_write);
+        }
+        let upstream = (*sapi_module).send_headers;
+        if upstream.is_some() {
+            UPSTREAM_SEND_HEADERS = upstream;
+            (*sapi_module).send_headers = Some(send_headers);
+        }
+    }
+}
+
+unsafe extern "C" fn ub_write(str_: *const c_char, str_length: usize) -> usize {
+    let written = unsafe {
+        match UPSTREAM_UB_WRITE {
+            Some(upstream) => upstream(str_, str_length),
+            None => 0,
+        }
+    };
+    BODY_SIZE.with(|size| size.set(size.get() + written as u64));
+    written
+}
+
+unsafe extern "C" fn send_headers(sapi_headers: *mut sys::sapi_headers_struct) -> c_int {
+    let pending = PENDING_HEADERS.with(|pending| pending.take());
+    if !pending.is_empty() {
+        unsafe {
+            // sapi_send_headers has already set headers_sent, which sapi_header_op would reject. It
+            // is cleared while adding our headers, so that they also go through the SAPI's
+            // header_handler (eg apache2handler writes headers straight to the request).
+            let headers_sent = sg!(headers_sent);
+            sg!(headers_sent) = Default::default();
+            for header in pending {
+                tracing::debug!("SAPI::adding response header {}", header.line);
+                add_header(&header);
+            }
+            sg!(headers_sent) = headers_sent;
+        }
+    }
+    unsafe {
+        match UPSTREAM_SEND_HEADERS {
+            Some(upstream) => upstream(sapi_headers),
+            None => 0,
+        }
+    }
+}
+
+unsafe fn add_header(header: &PendingHeader) {
+    let op = if header.replace {
+        sys::sapi_header_op_enum_SAPI_HEADER_REPLACE
+    } else {
+        sys::sapi_header_op_enum_SAPI_HEADER_ADD
+    };
+    let mut line = sys::sapi_header_line {
+        line: header.line.as_ptr() as _,
+        line_len: header.line.len(),
+        response_code: 0,
+    };
+    unsafe {
+        sys::sapi_header_op(op, &mut line as *mut sys::sapi_header_line as *mut c_void);
+    }
+}
+
+/// Set the headers to add to the current request's response, when headers are sent.
+pub fn set_pending_headers(headers: Vec<PendingHeader>) {
+    PENDING_HEADERS.with(|pending| *pending.borrow_mut() = headers);
+}
+
+/// Reset the response body size, at the start of a request.
+pub fn reset_body_size() {
+    BODY_SIZE.with(|size| size.set(0));
+}
+
+/// Response body bytes written so far by the current request.
+pub fn body_size() -> u64 {
+    BODY_SIZE.with(|size| size.get())
+}
+
+/// Response headers set by the current request, as "Name: value" lines.
+pub fn response_headers() -> Vec<String> {
+    let mut headers = Vec::new();
+    unsafe {
+        let mut element = sg!(sapi_headers).headers.head;
+        while !element.is_null() {
+            let header = (*element).data.as_ptr() as *const sys::sapi_header_struct;
+            if !(*header).header.is_null() {
+                let bytes = std::slice::from_raw_parts((*header).header as *const u8, (*header).header_len);
+                headers.push(String::from_utf8_lossy(bytes).into_owned());
+            }
+            element = (*element).next;
+        }
+    }
+    headers
+}
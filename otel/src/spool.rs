@@ -0,0 +1,666 @@
+// Disk-backed retry spool. When an OTLP export fails (eg the collector is restarting), the batch is
+// written to the spool directory as an encoded OTLP request. After a later successful export, by
+// any worker with the same exporter configuration, spooled batches are replayed to the endpoint,
+// one per export so that exports are not held up.
+// Files are claimed by renaming before replay, so that concurrent workers never send one twice.
+use crate::{
+    config::{
+        self,
+        otlp::{self, OtlpProtocol, Signal, TlsConfig},
+    },
+    otlp_encode::{encode_logs, encode_spans},
+    runtime::init_tokio_runtime,
+};
+use opentelemetry_proto::{
+    tonic::collector::{
+        logs::v1::{logs_service_client::LogsServiceClient, ExportLogsServiceRequest},
+        trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    },
+    transform::common::tonic::ResourceAttributesWithSchema,
+};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    logs::{LogBatch, LogExporter},
+    trace::{SpanData, SpanExporter},
+    Resource,
+};
+use flate2::write::GzEncoder;
+use opentelemetry_otlp::Compression;
+use phper::ini::ini_get;
+use prost::Message;
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    fmt,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tonic::{
+    codec::CompressionEncoding,
+    metadata::MetadataMap,
+    transport::{Channel, Endpoint},
+};
+
+const REPLAY_INTERVAL: Duration = Duration::from_secs(10);
+/// Batches replayed per successful export. Replay blocks the export, so this is kept small.
+const REPLAY_MAX_FILES: usize = 1;
+const STALE_CLAIM: Duration = Duration::from_secs(300);
+
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// Error from sending a spooled batch.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The endpoint is (still) unavailable, keep the batch.
+    Retryable(String),
+    /// The endpoint rejected the batch, discard it.
+    Permanent(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Retryable(err) | ReplayError::Permanent(err) => f.write_str(err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Spool {
+    dir: PathBuf,
+    root: PathBuf,
+    max_size: u64,
+}
+
+impl Spool {
+    /// Spool for the current exporter configuration, if enabled. OTEL_PHP_SPOOL_DIR and
+    /// OTEL_PHP_SPOOL_MAX_SIZE (MB) take precedence over otel.spool.dir and otel.spool.max_size.
+    /// Each destination (see config::env::spool_key) gets its own subdirectory, so that batches are
+    /// only replayed to the endpoint they were meant for.
+    pub fn from_config() -> Option<Self> {
+        let root = config::env::var("OTEL_PHP_SPOOL_DIR")
+            .ok()
+            .or_else(|| {
+                ini_get::<Option<&CStr>>(config::ini::OTEL_SPOOL_DIR)
+                    .and_then(|dir| dir.to_str().ok())
+                    .map(str::to_string)
+            })
+            .map(|dir| dir.trim().to_string())
+            .filter(|dir| !dir.is_empty())?;
+        let max_size_mb = config::env::var("OTEL_PHP_SPOOL_MAX_SIZE")
+            .ok()
+            .and_then(|size| size.trim().parse::<u64>().ok())
+            .unwrap_or_else(|| ini_get::<i64>(config::ini::OTEL_SPOOL_MAX_SIZE).max(0) as u64);
+        let root = PathBuf::from(root);
+        Some(Self::new(root.join(config::env::spool_key()), root, max_size_mb * 1024 * 1024))
+    }
+
+    pub fn new(dir: PathBuf, root: PathBuf, max_size: u64) -> Self {
+        Self { dir, root, max_size }
+    }
+
+    /// Write a failed batch to the spool, then remove the oldest batches if the spool (across all
+    /// exporter configurations) is over its maximum size.
+    pub fn write(&self, signal: Signal, payload: &[u8]) {
+        if let Err(err) = fs::create_dir_all(&self.dir) {
+            tracing::warn!("Failed to create spool directory {:?}: {}", self.dir, err);
+            return;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let name = format!("{}-{:020}-{}-{}.otlp", signal.name(), now, process::id(), SEQUENCE.fetch_add(1, Ordering::Relaxed));
+        let tmp_path = self.dir.join(format!(".{}.tmp", name));
+        let result = fs::write(&tmp_path, payload).and_then(|_| fs::rename(&tmp_path, self.dir.join(&name)));
+        match result {
+            Ok(_) => tracing::info!("Spooled failed {} batch to {:?}", signal.name(), name),
+            Err(err) => {
+                tracing::warn!("Failed to spool {} batch: {}", signal.name(), err);
+                let _ = fs::remove_file(&tmp_path);
+            }
+        }
+        self.enforce_max_size();
+    }
+
+    /// Replay up to max_files spooled batches for a signal, oldest first, stopping at the first
+    /// retryable failure. Returns the number of batches sent.
+    pub fn replay<F: FnMut(&[u8]) -> Result<(), ReplayError>>(&self, signal: Signal, max_files: usize, mut send: F) -> usize {
+        self.release_stale_claims();
+        let mut sent = 0;
+        for path in self.list(Some(signal)).into_iter().take(max_files) {
+            let claimed = path.with_extension(format!("claimed-{}", process::id()));
+            if fs::rename(&path, &claimed).is_err() {
+                // replayed (or claimed) by another worker
+                continue;
+            }
+            // a claim is only considered stale some time after it was made, not after the batch was written
+            let _ = fs::File::options().write(true).open(&claimed).and_then(|f| f.set_modified(SystemTime::now()));
+            let payload = match fs::read(&claimed) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    tracing::warn!("Failed to read spooled batch {:?}: {}", claimed, err);
+                    let _ = fs::remove_file(&claimed);
+                    continue;
+                }
+            };
+            match send(&payload) {
+                Ok(_) => {
+                    sent += 1;
+                    let _ = fs::remove_file(&claimed);
+                }
+                Err(ReplayError::Permanent(err)) => {
+                    tracing::warn!("Discarding spooled batch {:?}, rejected by endpoint: {}", path, err);
+                    let _ = fs::remove_file(&claimed);
+                }
+                Err(ReplayError::Retryable(err)) => {
+                    tracing::debug!("Replay of spooled batches stopped, endpoint unavailable: {}", err);
+                    let _ = fs::rename(&claimed, &path);
+                    break;
+                }
+            }
+        }
+        if sent > 0 {
+            tracing::info!("Replayed {} spooled {} batches", sent, signal.name());
+        }
+        sent
+    }
+
+    /// Spooled batches (of one signal, or all) in this spool's directory, oldest first.
+    fn list(&self, signal: Option<Signal>) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                name.ends_with(".otlp") && signal.is_none_or(|signal| name.starts_with(&format!("{}-", signal.name())))
+            })
+            .collect();
+        sort_oldest_first(&mut paths);
+        paths
+    }
+
+    /// Return batches claimed by a worker which did not finish replaying them (eg it crashed).
+    fn release_stale_claims(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let is_claim = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.starts_with("claimed-"));
+            if is_claim && modified_before(&path, STALE_CLAIM) {
+                let _ = fs::rename(&path, path.with_extension("otlp"));
+            }
+        }
+    }
+
+    fn enforce_max_size(&self) {
+        let mut files: Vec<(PathBuf, u64)> = Vec::new();
+        if let Ok(dirs) = fs::read_dir(&self.root) {
+            for dir in dirs.flatten().filter(|entry| entry.path().is_dir()) {
+                let spool = Spool::new(dir.path(), self.root.clone(), self.max_size);
+                files.extend(spool.list(None).into_iter().map(|path| {
+                    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    (path, size)
+                }));
+            }
+        }
+        let mut total: u64 = files.iter().map(|(_, size)| size).sum();
+        if total <= self.max_size {
+            return;
+        }
+        files.sort_by_key(|(path, _)| spooled_at(path));
+        for (path, size) in files {
+            if total <= self.max_size {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                tracing::warn!("Spool is over its maximum size, dropped oldest batch {:?}", path);
+                total -= size;
+            }
+        }
+    }
+}
+
+/// Spool time (fixed-width nanoseconds) and name, from a file name of the form
+/// {signal}-{timestamp}-{pid}-{sequence}.otlp
+fn spooled_at(path: &Path) -> (String, String) {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    (name.split('-').nth(1).unwrap_or_default().to_string(), name.to_string())
+}
+
+fn sort_oldest_first(paths: &mut [PathBuf]) {
+    paths.sort_by_key(|path| spooled_at(path));
+}
+
+fn modified_before(path: &Path, age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|elapsed| elapsed >= age)
+}
+
+/// Sends export requests to the OTLP endpoint: spooled (already encoded) batches, and the shared
+/// span pipeline's requests.
+#[derive(Debug)]
+pub enum RawSender {
+    Http(HttpSender),
+    Grpc(GrpcSender),
+}
+
+impl RawSender {
+    pub fn new(signal: Signal, protocol: OtlpProtocol) -> Self {
+        let otlp_config = otlp::get_otlp_config(signal, protocol.is_http());
+        let timeout = otlp_config.timeout.unwrap_or(otlp::DEFAULT_TIMEOUT);
+        if protocol.is_http() {
+            RawSender::Http(HttpSender {
+                url: otlp_config.endpoint.unwrap_or_else(|| format!("http://localhost:4318{}", signal.http_path())),
+                headers: otlp_config.headers,
+                timeout,
+                tls: otlp_config.tls,
+                json: protocol == OtlpProtocol::HttpJson,
+                compression: otlp_config.compression,
+                client: OnceLock::new(),
+            })
+        } else {
+            RawSender::Grpc(GrpcSender {
+                endpoint: otlp_config.endpoint.unwrap_or_else(|| "http://localhost:4317".to_string()),
+                headers: otlp_config.headers,
+                timeout,
+                tls: otlp_config.tls,
+                compression: otlp_config.compression,
+                channel: Mutex::new(None),
+            })
+        }
+    }
+
+    /// Send a spooled (protobuf-encoded) batch.
+    pub fn send(&self, signal: Signal, payload: &[u8]) -> Result<(), ReplayError> {
+        match (self, signal) {
+            (RawSender::Http(http), _) if http.json => http.post("application/json", to_json(signal, payload)?.into_bytes()),
+            (RawSender::Http(http), _) => http.post("application/x-protobuf", payload.to_vec()),
+            (RawSender::Grpc(_), Signal::Traces) => {
+                let request = ExportTraceServiceRequest::decode(payload)
+                    .map_err(|err| ReplayError::Permanent(err.to_string()))?;
+                self.send_traces(&request)
+            }
+            (RawSender::Grpc(grpc), Signal::Logs) => {
+                let request = ExportLogsServiceRequest::decode(payload)
+                    .map_err(|err| ReplayError::Permanent(err.to_string()))?;
+                grpc.export(|channel, compression, metadata| async move {
+                    let mut client = LogsServiceClient::new(channel);
+                    if let Some(compression) = compression {
+                        client = client.send_compressed(compression);
+                    }
+                    let mut request = tonic::Request::new(request);
+                    *request.metadata_mut() = metadata;
+                    client.export(request).await.map(|_| ())
+                })
+            }
+        }
+    }
+
+    /// Send a trace export request, which may hold the spans of several resources.
+    pub fn send_traces(&self, request: &ExportTraceServiceRequest) -> Result<(), ReplayError> {
+        match self {
+            RawSender::Http(http) if http.json => {
+                let body = serde_json::to_vec(request).map_err(|err| ReplayError::Permanent(err.to_string()))?;
+                http.post("application/json", body)
+            }
+            RawSender::Http(http) => http.post("application/x-protobuf", request.encode_to_vec()),
+            RawSender::Grpc(grpc) => {
+                let request = request.clone();
+                grpc.export(|channel, compression, metadata| async move {
+                    let mut client = TraceServiceClient::new(channel);
+                    if let Some(compression) = compression {
+                        client = client.send_compressed(compression);
+                    }
+                    let mut request = tonic::Request::new(request);
+                    *request.metadata_mut() = metadata;
+                    client.export(request).await.map(|_| ())
+                })
+            }
+        }
+    }
+}
+
+/// OTLP/http sender. The client is built on first use, and reused.
+#[derive(Debug)]
+pub struct HttpSender {
+    url: String,
+    headers: HashMap<String, String>,
+    timeout: Duration,
+    tls: TlsConfig,
+    json: bool,
+    compression: Option<Compression>,
+    client: OnceLock<reqwest::blocking::Client>,
+}
+
+impl HttpSender {
+    fn post(&self, content_type: &str, body: Vec<u8>) -> Result<(), ReplayError> {
+        let client = match self.client.get() {
+            Some(client) => client,
+            None => {
+                let client = otlp::http_client(self.timeout, &self.tls)
+                    .map_err(|err| ReplayError::Retryable(err.to_string()))?;
+                self.client.get_or_init(|| client)
+            }
+        };
+        let mut request = client.post(&self.url).header("content-type", content_type);
+        request = match self.compression {
+            Some(Compression::Gzip) => request.header("content-encoding", "gzip").body(gzip(&body)?),
+            Some(Compression::Zstd) => request.header("content-encoding", "zstd").body(
+                zstd::bulk::compress(&body, 0).map_err(|err| ReplayError::Permanent(err.to_string()))?,
+            ),
+            _ => request.body(body),
+        };
+        for (key, value) in &self.headers {
+            request = request.header(key.as_str(), value.as_str());
+        }
+        match request.send() {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => match response.status().as_u16() {
+                429 | 502 | 503 | 504 => Err(ReplayError::Retryable(format!("http status {}", response.status()))),
+                _ => Err(ReplayError::Permanent(format!("http status {}", response.status()))),
+            },
+            Err(err) => Err(ReplayError::Retryable(err.to_string())),
+        }
+    }
+}
+
+fn gzip(body: &[u8]) -> Result<Vec<u8>, ReplayError> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(body)
+        .and_then(|_| encoder.finish())
+        .map_err(|err| ReplayError::Permanent(err.to_string()))
+}
+
+/// OTLP/gRPC sender. The channel is connected on first use, and reused (tonic reconnects it if the
+/// connection is lost).
+#[derive(Debug)]
+pub struct GrpcSender {
+    endpoint: String,
+    headers: HashMap<String, String>,
+    timeout: Duration,
+    tls: TlsConfig,
+    compression: Option<Compression>,
+    channel: Mutex<Option<Channel>>,
+}
+
+impl GrpcSender {
+    /// The channel, which must be created within the tokio runtime.
+    fn channel(&self) -> Result<Channel, ReplayError> {
+        let mut channel = match self.channel.lock() {
+            Ok(channel) => channel,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(channel) = channel.as_ref() {
+            return Ok(channel.clone());
+        }
+        let mut endpoint = Endpoint::from_shared(self.endpoint.clone())
+            .map_err(|err| ReplayError::Permanent(err.to_string()))?;
+        if let Some(tls_config) = otlp::grpc_tls_config(&self.endpoint, &self.tls) {
+            endpoint = endpoint
+                .tls_config(tls_config)
+                .map_err(|err| ReplayError::Permanent(err.to_string()))?;
+        }
+        // connect lazily, so that the lock is not held while connecting
+        let connected = endpoint.timeout(self.timeout).connect_lazy();
+        *channel = Some(connected.clone());
+        Ok(connected)
+    }
+
+    /// Run an export over the channel, with the configured compression and headers.
+    fn export<F, Fut>(&self, export: F) -> Result<(), ReplayError>
+    where
+        F: FnOnce(Channel, Option<CompressionEncoding>, MetadataMap) -> Fut,
+        Fut: Future<Output = Result<(), tonic::Status>>,
+    {
+        let compression = match self.compression {
+            Some(Compression::Gzip) => Some(CompressionEncoding::Gzip),
+            Some(Compression::Zstd) => Some(CompressionEncoding::Zstd),
+            _ => None,
+        };
+        init_tokio_runtime().block_on(async {
+            let channel = self.channel()?;
+            export(channel, compression, otlp::to_metadata(&self.headers))
+                .await
+                .map_err(|status| match status.code() {
+                    tonic::Code::Cancelled | tonic::Code::DeadlineExceeded | tonic::Code::ResourceExhausted
+                    | tonic::Code::Aborted | tonic::Code::OutOfRange | tonic::Code::Unavailable
+                    | tonic::Code::DataLoss => ReplayError::Retryable(status.to_string()),
+                    _ => ReplayError::Permanent(status.to_string()),
+                })
+        })
+    }
+}
+
+/// Re-encode a spooled (protobuf) batch as OTLP-JSON
+fn to_json(signal: Signal, payload: &[u8]) -> Result<String, ReplayError> {
+    let json = match signal {
+        Signal::Traces => ExportTraceServiceRequest::decode(payload)
+            .map_err(|err| ReplayError::Permanent(err.to_string()))
+            .map(|request| serde_json::to_string(&request))?,
+        Signal::Logs => ExportLogsServiceRequest::decode(payload)
+            .map_err(|err| ReplayError::Permanent(err.to_string()))
+            .map(|request| serde_json::to_string(&request))?,
+    };
+    json.map_err(|err| ReplayError::Permanent(err.to_string()))
+}
+
+/// Shared state of the spooling exporters: the spool, and when it was last replayed.
+#[derive(Debug)]
+struct SpoolState {
+    spool: Spool,
+    sender: RawSender,
+    last_replay: Mutex<Option<Instant>>,
+}
+
+impl SpoolState {
+    fn new(spool: Spool, sender: RawSender) -> Self {
+        Self {
+            spool,
+            sender,
+            last_replay: Mutex::new(None),
+        }
+    }
+
+    /// After a successful export the endpoint is reachable, so replay a spooled batch. While
+    /// batches are being replayed, the next export replays another; otherwise the spool is checked
+    /// at most every REPLAY_INTERVAL, to avoid listing the directory on every export.
+    fn maybe_replay(&self, signal: Signal) {
+        let mut last_replay = match self.last_replay.lock() {
+            Ok(last_replay) => last_replay,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if last_replay.is_some_and(|last| last.elapsed() < REPLAY_INTERVAL) {
+            return;
+        }
+        let sent = self.spool.replay(signal, REPLAY_MAX_FILES, |payload| self.sender.send(signal, payload));
+        *last_replay = if sent > 0 { None } else { Some(Instant::now()) };
+    }
+}
+
+/// OTLP span exporter which spools failed batches, and replays them once exports succeed.
+#[derive(Debug)]
+pub struct SpoolingSpanExporter<E> {
+    inner: E,
+    state: SpoolState,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl<E: SpanExporter> SpoolingSpanExporter<E> {
+    pub fn new(inner: E, spool: Spool, sender: RawSender) -> Self {
+        Self {
+            inner,
+            state: SpoolState::new(spool, sender),
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for SpoolingSpanExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        // the inner exporter takes the batch, so keep a copy to spool (only encoded on failure)
+        let result = self.inner.export(batch.clone()).await;
+        match result {
+            Ok(_) => self.state.maybe_replay(Signal::Traces),
+            Err(_) => self.state.spool.write(Signal::Traces, &encode_spans(batch, &self.resource)),
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+        self.inner.set_resource(resource);
+    }
+}
+
+/// OTLP log exporter which spools failed batches, and replays them once exports succeed.
+#[derive(Debug)]
+pub struct SpoolingLogExporter<E> {
+    inner: E,
+    state: SpoolState,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl<E: LogExporter> SpoolingLogExporter<E> {
+    pub fn new(inner: E, spool: Spool, sender: RawSender) -> Self {
+        Self {
+            inner,
+            state: SpoolState::new(spool, sender),
+            resource: ResourceAttributesWithSchema::default(),
+        }
+    }
+}
+
+impl<E: LogExporter> LogExporter for SpoolingLogExporter<E> {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        // LogBatch can't be cloned, but a batch can be built again from its records
+        let records: Vec<_> = batch.iter().collect();
+        let result = self.inner.export(LogBatch::new(&records)).await;
+        match result {
+            Ok(_) => self.state.maybe_replay(Signal::Logs),
+            Err(_) => self.state.spool.write(Signal::Logs, &encode_logs(LogBatch::new(&records), &self.resource)),
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Sends trace export requests built by the shared span pipeline (one request per batch, which may
+/// hold several resources), spooling those which fail, and replays the spool once sends succeed.
+#[derive(Debug)]
+pub struct SpoolingRequestSender {
+    state: SpoolState,
+}
+
+impl SpoolingRequestSender {
+    pub fn new(spool: Spool, sender: RawSender) -> Self {
+        Self {
+            state: SpoolState::new(spool, sender),
+        }
+    }
+
+    pub fn send_traces(&self, request: &ExportTraceServiceRequest) -> Result<(), ReplayError> {
+        let result = self.state.sender.send_traces(request);
+        match &result {
+            Ok(_) => self.state.maybe_replay(Signal::Traces),
+            Err(ReplayError::Retryable(_)) => self.state.spool.write(Signal::Traces, &request.encode_to_vec()),
+            // the endpoint would reject it again
+            Err(ReplayError::Permanent(_)) => {}
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_spool(name: &str, max_size: u64) -> Spool {
+        let root = std::env::temp_dir().join(format!("otel-spool-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        Spool::new(root.join("key"), root, max_size)
+    }
+
+    #[test]
+    fn test_replays_oldest_first_and_removes_sent() {
+        let spool = temp_spool("replay", 1024);
+        spool.write(Signal::Traces, b"one");
+        spool.write(Signal::Logs, b"log");
+        spool.write(Signal::Traces, b"two");
+        let mut sent = Vec::new();
+        assert_eq!(spool.replay(Signal::Traces, 10, |payload| {
+            sent.push(payload.to_vec());
+            Ok(())
+        }), 2);
+        assert_eq!(sent, vec![b"one".to_vec(), b"two".to_vec()]);
+        assert!(spool.list(Some(Signal::Traces)).is_empty());
+        assert_eq!(spool.list(Some(Signal::Logs)).len(), 1);
+        fs::remove_dir_all(&spool.root).unwrap();
+    }
+
+    #[test]
+    fn test_retryable_failure_keeps_batch() {
+        let spool = temp_spool("retry", 1024);
+        spool.write(Signal::Traces, b"one");
+        spool.write(Signal::Traces, b"two");
+        let mut attempts = 0;
+        assert_eq!(spool.replay(Signal::Traces, 10, |_| {
+            attempts += 1;
+            Err(ReplayError::Retryable("unavailable".to_string()))
+        }), 0);
+        assert_eq!(attempts, 1);
+        assert_eq!(spool.list(Some(Signal::Traces)).len(), 2);
+        fs::remove_dir_all(&spool.root).unwrap();
+    }
+
+    #[test]
+    fn test_gzip() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+        let mut decoded = Vec::new();
+        GzDecoder::new(gzip(b"payload").unwrap().as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"payload");
+    }
+
+    #[test]
+    fn test_drops_oldest_over_max_size() {
+        let spool = temp_spool("max-size", 10);
+        spool.write(Signal::Traces, b"12345");
+        spool.write(Signal::Traces, b"67890");
+        spool.write(Signal::Traces, b"abcde");
+        let mut sent = Vec::new();
+        spool.replay(Signal::Traces, 10, |payload| {
+            sent.push(payload.to_vec());
+            Ok(())
+        });
+        assert_eq!(sent, vec![b"67890".to_vec(), b"abcde".to_vec()]);
+        fs::remove_dir_all(&spool.root).unwrap();
+    }
+}
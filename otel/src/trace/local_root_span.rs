@@ -1,10 +1,12 @@
 use phper::{
+    arrays::IterKey,
     classes::{ClassEntity, Visibility},
-    functions::{ReturnType},
-    types::ReturnTypeHint,
+    functions::{Argument, ReturnType},
+    types::{ArgumentTypeHint, ReturnTypeHint},
 };
 use std::{
     cell::RefCell,
+    collections::HashMap,
     convert::Infallible,
     sync::Arc,
 };
@@ -12,9 +14,11 @@ use crate::{
     context::{
         storage,
     },
+    request,
     trace::{
         non_recording_span::{NonRecordingSpanClass},
         span::SpanClass,
+        span_builder::span_kind_from_long,
     },
 };
 use opentelemetry::Context;
@@ -53,6 +57,43 @@ pub fn make_local_root_span_class(
         .return_type(ReturnType::new(ReturnTypeHint::ClassEntry(String::from(r"OpenTelemetry\API\Span\SpanInterface"))))
     ;
 
+    // Long-running workers: start a new local root span for each unit of work
+    let start_span_class = span_class.clone();
+    class
+        .add_static_method("start", Visibility::Public, move |arguments| {
+            let name = arguments[0].expect_z_str()?.to_str()?.to_string();
+            let mut carrier = HashMap::new();
+            if let Some(arr) = arguments.get(1).and_then(|arg| arg.as_z_arr()) {
+                for (key, value) in arr.iter() {
+                    if let (IterKey::ZStr(key), Some(value)) = (key, value.as_z_str()) {
+                        if let (Ok(key), Ok(value)) = (key.to_str(), value.to_str()) {
+                            carrier.insert(key.to_lowercase(), value.to_string());
+                        }
+                    }
+                }
+            }
+            let kind = span_kind_from_long(arguments.get(2).and_then(|arg| arg.as_long()).unwrap_or(1));
+            let instance_id = request::start_job_span(name, carrier, kind);
+            let mut object = start_span_class.clone().init_object()?;
+            *object.as_mut_state() = None;
+            object.set_property("context_id", instance_id.unwrap_or(0) as i64);
+            object.set_property("is_local_root", true);
+            Ok::<_, phper::Error>(object)
+        })
+        .argument(Argument::new("name").with_type_hint(ArgumentTypeHint::String))
+        .argument(Argument::new("carrier").optional().with_type_hint(ArgumentTypeHint::Array).with_default_value("[]"))
+        .argument(Argument::new("kind").optional().with_type_hint(ArgumentTypeHint::Int).with_default_value("1"))
+        .return_type(ReturnType::new(ReturnTypeHint::ClassEntry(String::from(r"OpenTelemetry\API\Span\SpanInterface"))))
+    ;
+
+    class
+        .add_static_method("end", Visibility::Public, |_| {
+            request::end_job_span();
+            Ok::<_, Infallible>(())
+        })
+        .return_type(ReturnType::new(ReturnTypeHint::Void))
+    ;
+
     class
 }
 
@@ -11,3 +11,4 @@ pub mod tracer_interface;
 pub mod tracer_provider;
 pub mod tracer_provider_interface;
 pub mod propagation;
+pub mod shared_pipeline;
\ No newline at end of file
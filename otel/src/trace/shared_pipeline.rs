@@ -0,0 +1,343 @@
+// Shared span export pipeline. Each app (resource) still gets its own SdkTracerProvider, but with
+// batching enabled their spans are fed to one batching thread and exporter per export config
+// (endpoint, headers etc), instead of each provider owning a BatchSpanProcessor thread and
+// exporter connection. A batch can hold the spans of several resources: OTLP batches (and agent or
+// file ones) are sent as one export request, with a ResourceSpans per resource.
+use crate::{
+    agent::AgentSpanExporter,
+    config,
+    file_exporter::FileSpanExporter,
+    otlp_encode::spans_request_by_resource,
+    spool::{RawSender, SpoolingRequestSender},
+    trace::tracer_provider::TraceExporter,
+};
+use once_cell::sync::Lazy;
+use opentelemetry::Context;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    trace::{Span, SpanData, SpanExporter, SpanProcessor},
+    Resource,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+static PIPELINES: Lazy<Mutex<HashMap<(u32, String), Arc<SharedPipeline>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+enum Message {
+    Span(Arc<Resource>, SpanData),
+    Flush(SyncSender<()>),
+    Shutdown(SyncSender<()>),
+}
+
+struct BatchConfig {
+    scheduled_delay: Duration,
+    max_queue_size: usize,
+    max_export_batch_size: usize,
+}
+
+impl BatchConfig {
+    /// Read from OTEL_BSP_* variables, with the same defaults as the SDK
+    fn from_env() -> Self {
+        let get = |name: &str, default: u64| {
+            config::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            scheduled_delay: Duration::from_millis(get("OTEL_BSP_SCHEDULE_DELAY", 5000)),
+            max_queue_size: get("OTEL_BSP_MAX_QUEUE_SIZE", 2048).max(1) as usize,
+            max_export_batch_size: get("OTEL_BSP_MAX_EXPORT_BATCH_SIZE", 512).max(1) as usize,
+        }
+    }
+}
+
+/// The exporter of a pipeline. The opentelemetry-otlp exporter has a single resource, so OTLP
+/// requests are built here and sent by a RawSender. Exporters which are not OTLP-based (console,
+/// memory, zipkin) are called once per resource.
+#[derive(Debug)]
+pub enum PipelineExporter {
+    Otlp(RawSender),
+    Spooling(SpoolingRequestSender),
+    Agent(AgentSpanExporter),
+    File(FileSpanExporter),
+    PerResource(TraceExporter),
+}
+
+impl From<TraceExporter> for PipelineExporter {
+    fn from(exporter: TraceExporter) -> Self {
+        match exporter {
+            TraceExporter::Agent(exporter) => PipelineExporter::Agent(exporter),
+            TraceExporter::File(exporter) => PipelineExporter::File(exporter),
+            exporter => PipelineExporter::PerResource(exporter),
+        }
+    }
+}
+
+impl PipelineExporter {
+    fn export(&mut self, batches: Vec<(Arc<Resource>, Vec<SpanData>)>) {
+        let request = |batches: Vec<(Arc<Resource>, Vec<SpanData>)>| {
+            spans_request_by_resource(batches.into_iter().map(|(resource, spans)| (resource.as_ref().into(), spans)))
+        };
+        let result = match self {
+            PipelineExporter::Otlp(sender) => sender.send_traces(&request(batches)).map_err(|err| err.to_string()),
+            PipelineExporter::Spooling(sender) => sender.send_traces(&request(batches)).map_err(|err| err.to_string()),
+            PipelineExporter::Agent(exporter) => exporter.export_request(&request(batches)).map_err(|err| err.to_string()),
+            PipelineExporter::File(exporter) => exporter.export_request(&request(batches)).map_err(|err| err.to_string()),
+            PipelineExporter::PerResource(exporter) => {
+                for (resource, spans) in batches {
+                    exporter.set_resource(&resource);
+                    if let Err(err) = futures_executor::block_on(exporter.export(spans)) {
+                        tracing::warn!("shared span export failed: {:?}", err);
+                    }
+                }
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            tracing::warn!("shared span export failed: {}", err);
+        }
+    }
+
+    fn force_flush(&mut self) {
+        if let PipelineExporter::PerResource(exporter) = self {
+            let _ = exporter.force_flush();
+        }
+    }
+
+    fn shutdown(&mut self) {
+        if let PipelineExporter::PerResource(exporter) = self {
+            let _ = exporter.shutdown();
+        }
+    }
+}
+
+pub struct SharedPipeline {
+    sender: SyncSender<Message>,
+    dropped: AtomicUsize,
+}
+
+impl fmt::Debug for SharedPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedPipeline").finish()
+    }
+}
+
+impl SharedPipeline {
+    fn start(exporter: PipelineExporter) -> Self {
+        let config = BatchConfig::from_env();
+        let (sender, receiver) = mpsc::sync_channel(config.max_queue_size);
+        thread::Builder::new()
+            .name("otel-shared-span-export".to_string())
+            .spawn(move || run(exporter, receiver, config))
+            .expect("Failed to start shared span export thread");
+        Self {
+            sender,
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    fn send(&self, resource: Arc<Resource>, span: SpanData) {
+        match self.sender.try_send(Message::Span(resource, span)) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped == 1 || dropped % 1000 == 0 {
+                    tracing::warn!("shared span export queue is full, {} spans dropped", dropped);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                tracing::warn!("shared span export thread is not running, span dropped");
+            }
+        }
+    }
+
+    fn request(&self, message: fn(SyncSender<()>) -> Message) -> OTelSdkResult {
+        let (ack_sender, ack_receiver) = mpsc::sync_channel(1);
+        self.sender
+            .send(message(ack_sender))
+            .map_err(|_| OTelSdkError::InternalFailure("shared span export thread is not running".to_string()))?;
+        ack_receiver
+            .recv_timeout(FLUSH_TIMEOUT)
+            .map_err(|_| OTelSdkError::Timeout(FLUSH_TIMEOUT))
+    }
+
+    pub fn flush(&self) -> OTelSdkResult {
+        self.request(Message::Flush)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.request(Message::Shutdown)
+    }
+}
+
+fn run(mut exporter: PipelineExporter, receiver: mpsc::Receiver<Message>, config: BatchConfig) {
+    let mut batch: Vec<(Arc<Resource>, SpanData)> = Vec::new();
+    let mut deadline = Instant::now() + config.scheduled_delay;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(Message::Span(resource, span)) => {
+                batch.push((resource, span));
+                if batch.len() >= config.max_export_batch_size {
+                    export_batch(&mut exporter, &mut batch);
+                }
+            }
+            Ok(Message::Flush(ack)) => {
+                export_batch(&mut exporter, &mut batch);
+                exporter.force_flush();
+                let _ = ack.send(());
+            }
+            Ok(Message::Shutdown(ack)) => {
+                export_batch(&mut exporter, &mut batch);
+                exporter.shutdown();
+                let _ = ack.send(());
+                return;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                export_batch(&mut exporter, &mut batch);
+                deadline = Instant::now() + config.scheduled_delay;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                export_batch(&mut exporter, &mut batch);
+                exporter.shutdown();
+                return;
+            }
+        }
+    }
+}
+
+/// Export a batch, grouped by resource.
+fn export_batch(exporter: &mut PipelineExporter, batch: &mut Vec<(Arc<Resource>, SpanData)>) {
+    if batch.is_empty() {
+        return;
+    }
+    exporter.export(group_by_resource(batch.drain(..)));
+}
+
+fn group_by_resource<I: IntoIterator<Item = (Arc<Resource>, SpanData)>>(batch: I) -> Vec<(Arc<Resource>, Vec<SpanData>)> {
+    let mut groups: Vec<(Arc<Resource>, Vec<SpanData>)> = Vec::new();
+    for (resource, span) in batch {
+        match groups.iter_mut().find(|(r, _)| Arc::ptr_eq(r, &resource) || **r == *resource) {
+            Some((_, spans)) => spans.push(span),
+            None => groups.push((resource, vec![span])),
+        }
+    }
+    groups
+}
+
+/// Span processor for one provider, which sends ended spans (with the provider's resource) to
+/// a shared pipeline.
+#[derive(Debug)]
+pub struct SharedPipelineProcessor {
+    pipeline: Arc<SharedPipeline>,
+    resource: Arc<Resource>,
+}
+
+impl SpanProcessor for SharedPipelineProcessor {
+    fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+    fn on_end(&self, span: SpanData) {
+        if !span.span_context.is_sampled() {
+            return;
+        }
+        self.pipeline.send(self.resource.clone(), span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.pipeline.flush()
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        // the pipeline is shared with other providers, so only flush this provider's spans
+        self.pipeline.flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = Arc::new(resource.clone());
+    }
+}
+
+/// Get a processor feeding the shared pipeline for the current export config, starting the
+/// pipeline (with an exporter from build_exporter) if necessary. Fails if the exporter cannot be
+/// built, in which case no pipeline is started.
+pub fn get_processor<F: FnOnce() -> anyhow::Result<PipelineExporter>>(build_exporter: F) -> anyhow::Result<SharedPipelineProcessor> {
+    let key = (process::id(), config::env::exporter_key());
+    let mut pipelines = PIPELINES.lock().unwrap();
+    let pipeline = match pipelines.entry(key) {
+        Entry::Occupied(entry) => entry.get().clone(),
+        Entry::Vacant(entry) => {
+            tracing::debug!("starting shared span export pipeline for key {:?}", entry.key());
+            entry.insert(Arc::new(SharedPipeline::start(build_exporter()?))).clone()
+        }
+    };
+    Ok(SharedPipelineProcessor {
+        pipeline,
+        resource: Arc::new(Resource::builder_empty().build()),
+    })
+}
+
+/// Whether batched span export should use a shared pipeline (otel.traces.shared_pipeline)
+pub fn is_enabled() -> bool {
+    phper::ini::ini_get::<bool>(config::ini::OTEL_TRACES_SHARED_PIPELINE)
+}
+
+/// Number of running pipelines, for phpinfo()
+pub fn pipeline_count() -> usize {
+    PIPELINES.lock().map(|pipelines| pipelines.len()).unwrap_or(0)
+}
+
+/// Export anything buffered by this process's pipelines.
+pub fn flush_all() {
+    let pid = process::id();
+    let pipelines: Vec<_> = {
+        let pipelines = PIPELINES.lock().unwrap();
+        pipelines.iter().filter(|((k_pid, _), _)| *k_pid == pid).map(|(_, pipeline)| pipeline.clone()).collect()
+    };
+    for pipeline in pipelines {
+        if let Err(err) = pipeline.flush() {
+            tracing::warn!("Failed to flush shared span export pipeline: {:?}", err);
+        }
+    }
+}
+
+/// In a forked child, discard pipelines inherited from the parent (whose threads only exist in
+/// the parent) without shutting them down.
+pub fn forget_inherited() {
+    let pid = process::id();
+    let mut pipelines = PIPELINES.lock().unwrap();
+    let inherited: Vec<_> = pipelines.keys().filter(|(k_pid, _)| *k_pid != pid).cloned().collect();
+    for key in inherited {
+        if let Some(pipeline) = pipelines.remove(&key) {
+            std::mem::forget(pipeline);
+        }
+    }
+}
+
+/// Export anything remaining, and stop all pipelines for this process.
+pub fn shutdown() {
+    let pid = process::id();
+    let pipelines: Vec<_> = {
+        let mut pipelines = PIPELINES.lock().unwrap();
+        let keys: Vec<_> = pipelines.keys().filter(|(k_pid, _)| *k_pid == pid).cloned().collect();
+        keys.into_iter().filter_map(|key| pipelines.remove_entry(&key)).collect()
+    };
+    for (key, pipeline) in pipelines {
+        tracing::debug!("shutting down shared span export pipeline for key {:?}", key);
+        if let Err(err) = pipeline.shutdown() {
+            tracing::warn!("Failed to shut down shared span export pipeline: {:?}", err);
+        }
+    }
+}
@@ -91,18 +91,7 @@ pub fn make_span_builder_class(span_class: SpanClass) -> ClassEntity<SpanBuilder
         .add_method("setSpanKind", Visibility::Public, |this, arguments| {
             let state = this.as_mut_state();
             let span_builder = state.span_builder.as_ref().expect("SpanBuilder not set");
-            let span_kind_int = arguments[0].expect_long()?;
-            let span_kind = match span_kind_int {
-                0 => SpanKind::Internal,
-                1 => SpanKind::Server,
-                2 => SpanKind::Client,
-                3 => SpanKind::Producer,
-                4 => SpanKind::Consumer,
-                _ => {
-                    //log a warning
-                    SpanKind::Internal
-                },
-            };
+            let span_kind = span_kind_from_long(arguments[0].expect_long()?);
             let new_span_builder = span_builder.clone().with_kind(span_kind);
             state.span_builder = Some(new_span_builder);
 
@@ -146,3 +135,18 @@ pub fn make_span_builder_class(span_class: SpanClass) -> ClassEntity<SpanBuilder
 
     class
 }
+
+/// Convert a SpanKind constant (as used by the PHP API) to a SpanKind.
+pub fn span_kind_from_long(span_kind: i64) -> SpanKind {
+    match span_kind {
+        0 => SpanKind::Internal,
+        1 => SpanKind::Server,
+        2 => SpanKind::Client,
+        3 => SpanKind::Producer,
+        4 => SpanKind::Consumer,
+        _ => {
+            tracing::warn!("unknown span kind {}, using internal", span_kind);
+            SpanKind::Internal
+        },
+    }
+}
@@ -5,29 +5,60 @@ use phper::{
 };
 use std::{
     convert::Infallible,
+    process,
 };
 use opentelemetry::{
     trace::{
         SpanBuilder,
         Tracer,
-    }
+        TracerProvider,
+    },
+    InstrumentationScope,
 };
 use opentelemetry_sdk::trace::SdkTracer;
-use crate::trace::span_builder::{
-    SpanBuilderState,
-    SpanBuilderClass,
+use crate::trace::{
+    span_builder::{
+        SpanBuilderState,
+        SpanBuilderClass,
+    },
+    tracer_provider,
 };
 
-pub type TracerClass = StateClass<Option<SdkTracer>>;
+pub type TracerClass = StateClass<Option<TracerState>>;
+
+/// A tracer, with the scope and process it was created for.
+pub struct TracerState {
+    tracer: SdkTracer,
+    scope: InstrumentationScope,
+    pid: u32,
+}
+
+impl TracerState {
+    pub fn new(tracer: SdkTracer, scope: InstrumentationScope) -> Self {
+        Self { tracer, scope, pid: process::id() }
+    }
+
+    /// The tracer, rebound to the current provider if it was created before pcntl_fork: the
+    /// inherited provider's processors export via threads which only exist in the parent.
+    fn tracer(&mut self) -> &SdkTracer {
+        let pid = process::id();
+        if self.pid != pid {
+            tracing::debug!("fork: rebinding tracer {:?} to the child's tracer provider", self.scope.name());
+            self.tracer = tracer_provider::get_tracer_provider().tracer_with_scope(self.scope.clone());
+            self.pid = pid;
+        }
+        &self.tracer
+    }
+}
 
 const TRACER_CLASS_NAME: &str = r"OpenTelemetry\API\Trace\Tracer";
 
 pub fn make_tracer_class(
     span_builder_class: SpanBuilderClass,
     tracer_interface: Interface,
-) -> ClassEntity<Option<SdkTracer>> {
+) -> ClassEntity<Option<TracerState>> {
     let mut class =
-        ClassEntity::<Option<SdkTracer>>::new_with_default_state_constructor(TRACER_CLASS_NAME);
+        ClassEntity::<Option<TracerState>>::new_with_default_state_constructor(TRACER_CLASS_NAME);
 
     class.implements(tracer_interface);
     class.add_method("__construct", Visibility::Private, |_, _| {
@@ -36,7 +67,7 @@ pub fn make_tracer_class(
 
     class
         .add_method("spanBuilder", Visibility::Public, move |this, arguments| {
-            let tracer: &SdkTracer = this.as_state().as_ref().unwrap();
+            let tracer: &SdkTracer = this.as_mut_state().as_mut().unwrap().tracer();
             let name = arguments[0].expect_z_str()?.to_str()?.to_string();
             let span_builder: SpanBuilder = tracer.span_builder(name);
             let my_span_builder = SpanBuilderState::new(span_builder, tracer.clone());
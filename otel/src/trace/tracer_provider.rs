@@ -4,27 +4,35 @@ use phper::{
     types::{ArgumentTypeHint, ReturnTypeHint},
 };
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::HashSet,
     convert::Infallible,
-    env,
     process,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use opentelemetry::{
     InstrumentationScope,
-    KeyValue,
     trace::TracerProvider,
 };
+use anyhow::Context as _;
 use opentelemetry_stdout::SpanExporter as StdoutSpanExporter;
+use opentelemetry_zipkin::ZipkinExporter;
 use opentelemetry_otlp::{
-    Protocol,
+    ExporterBuildError,
     SpanExporter as OtlpSpanExporter,
     WithExportConfig,
+    WithHttpConfig,
+    WithTonicConfig,
 };
 use opentelemetry_sdk::{
+    error::OTelSdkResult,
     trace::{
-        Sampler::AlwaysOff,
+        InMemorySpanExporter,
+        Sampler,
         SdkTracerProvider,
+        SpanData,
+        SpanExporter,
     },
     Resource,
 };
@@ -32,117 +40,343 @@ use once_cell::sync::{
     Lazy,
 };
 use crate::{
+    agent::{self, AgentSpanExporter},
+    config::{
+        self,
+        otlp::{self, OtlpProtocol, Signal},
+    },
+    file_exporter::{FileSpanExporter, JsonLinesFile},
+    provider_cache::{self, CacheLimits, ProviderCache, ProviderKey},
     request,
+    spool::{RawSender, Spool, SpoolingRequestSender, SpoolingSpanExporter},
     trace::{
         memory_exporter::MEMORY_EXPORTER,
-        tracer::TracerClass
+        shared_pipeline::{self, PipelineExporter},
+        tracer::{TracerClass, TracerState},
     },
     util,
     runtime::init_tokio_runtime,
 };
 
 const TRACER_PROVIDER_CLASS_NAME: &str = r"OpenTelemetry\API\Trace\TracerProvider";
+const ZIPKIN_DEFAULT_ENDPOINT: &str = "http://127.0.0.1:9411/api/v2/spans";
+const ZIPKIN_DEFAULT_TIMEOUT: Duration = Duration::from_millis(10_000);
 
 pub type TracerProviderClass = StateClass<()>;
 
-static TRACER_PROVIDERS: Lazy<Mutex<HashMap<(u32, String), Arc<SdkTracerProvider>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-static NOOP_TRACER_PROVIDER: Lazy<Arc<SdkTracerProvider>> = Lazy::new(|| {
-    Arc::new(SdkTracerProvider::builder()
+static TRACER_PROVIDERS: Lazy<Mutex<ProviderCache<SdkTracerProvider>>> = Lazy::new(|| Mutex::new(ProviderCache::new()));
+static NOOP_TRACER_PROVIDER: Lazy<Arc<SdkTracerProvider>> = Lazy::new(|| Arc::new(build_noop_provider()));
+
+thread_local! {
+    // providers leased by this thread's request (or FrankenPHP worker script), released at RSHUTDOWN
+    static LEASED_PROVIDERS: RefCell<HashSet<ProviderKey>> = RefCell::new(HashSet::new());
+}
+
+fn build_noop_provider() -> SdkTracerProvider {
+    SdkTracerProvider::builder()
         .with_resource(Resource::builder_empty().build())
-        .with_sampler(AlwaysOff)
-        .build())
-});
+        .with_sampler(Sampler::AlwaysOff)
+        .build()
+}
 
-//tracer provider per (service_name, resource_attributes) pair (from .env, if enabled)
+//tracer provider per effective SDK configuration (from .env, if enabled)
 fn get_tracer_provider_key() -> (u32, String) {
-    let pid = process::id();
-    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_default();
-    let resource_attrs = env::var("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default();
-    let key = format!("{}:{}", service_name, resource_attrs);
-    (pid, key)
+    (process::id(), config::env::provider_key())
+}
+
+/// Sampler from OTEL_TRACES_SAMPLER and OTEL_TRACES_SAMPLER_ARG, default parentbased_always_on
+fn get_sampler() -> Sampler {
+    let ratio = || {
+        config::env::var("OTEL_TRACES_SAMPLER_ARG")
+            .ok()
+            .and_then(|arg| arg.trim().parse::<f64>().ok())
+            .unwrap_or(1.0)
+    };
+    match config::env::var("OTEL_TRACES_SAMPLER").as_deref().unwrap_or("parentbased_always_on") {
+        "always_on" => Sampler::AlwaysOn,
+        "always_off" => Sampler::AlwaysOff,
+        "traceidratio" => Sampler::TraceIdRatioBased(ratio()),
+        "parentbased_always_on" => Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        "parentbased_always_off" => Sampler::ParentBased(Box::new(Sampler::AlwaysOff)),
+        "parentbased_traceidratio" => Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio()))),
+        other => {
+            tracing::warn!("Unsupported sampler {}, using parentbased_always_on", other);
+            Sampler::ParentBased(Box::new(Sampler::AlwaysOn))
+        }
+    }
+}
+
+/// The configured span exporter. An enum rather than a boxed trait object, since SpanExporter is
+/// not dyn-compatible.
+#[derive(Debug)]
+pub enum TraceExporter {
+    Console(StdoutSpanExporter),
+    Memory(InMemorySpanExporter),
+    Otlp(OtlpSpanExporter),
+    Agent(AgentSpanExporter),
+    Spooling(SpoolingSpanExporter<OtlpSpanExporter>),
+    File(FileSpanExporter),
+    Zipkin(ZipkinExporter),
+}
+
+impl SpanExporter for TraceExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        match self {
+            TraceExporter::Console(exporter) => exporter.export(batch).await,
+            TraceExporter::Memory(exporter) => exporter.export(batch).await,
+            TraceExporter::Otlp(exporter) => exporter.export(batch).await,
+            TraceExporter::Agent(exporter) => exporter.export(batch).await,
+            TraceExporter::Spooling(exporter) => exporter.export(batch).await,
+            TraceExporter::File(exporter) => exporter.export(batch).await,
+            TraceExporter::Zipkin(exporter) => exporter.export(batch).await,
+        }
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        match self {
+            TraceExporter::Console(exporter) => exporter.shutdown_with_timeout(timeout),
+            TraceExporter::Memory(exporter) => exporter.shutdown_with_timeout(timeout),
+            TraceExporter::Otlp(exporter) => exporter.shutdown_with_timeout(timeout),
+            TraceExporter::Agent(exporter) => exporter.shutdown_with_timeout(timeout),
+            TraceExporter::Spooling(exporter) => exporter.shutdown_with_timeout(timeout),
+            TraceExporter::File(exporter) => exporter.shutdown_with_timeout(timeout),
+            TraceExporter::Zipkin(exporter) => exporter.shutdown_with_timeout(timeout),
+        }
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        match self {
+            TraceExporter::Console(exporter) => exporter.force_flush(),
+            TraceExporter::Memory(exporter) => exporter.force_flush(),
+            TraceExporter::Otlp(exporter) => exporter.force_flush(),
+            TraceExporter::Agent(exporter) => exporter.force_flush(),
+            TraceExporter::Spooling(exporter) => exporter.force_flush(),
+            TraceExporter::File(exporter) => exporter.force_flush(),
+            TraceExporter::Zipkin(exporter) => exporter.force_flush(),
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        match self {
+            TraceExporter::Console(exporter) => exporter.set_resource(resource),
+            TraceExporter::Memory(exporter) => exporter.set_resource(resource),
+            TraceExporter::Otlp(exporter) => exporter.set_resource(resource),
+            TraceExporter::Agent(exporter) => exporter.set_resource(resource),
+            TraceExporter::Spooling(exporter) => exporter.set_resource(resource),
+            TraceExporter::File(exporter) => exporter.set_resource(resource),
+            TraceExporter::Zipkin(exporter) => exporter.set_resource(resource),
+        }
+    }
+}
+
+/// Build the exporter selected by OTEL_TRACES_EXPORTER and the OTLP protocol (or the
+/// agent socket, in place of OTLP)
+fn build_exporter() -> anyhow::Result<TraceExporter> {
+    match build_non_otlp_exporter()? {
+        Some(exporter) => Ok(exporter),
+        None => Ok(build_otlp_exporter(otlp::get_protocol(Signal::Traces))?),
+    }
+}
+
+/// The exporter selected by OTEL_TRACES_EXPORTER, or the agent exporter, unless OTLP is used
+fn build_non_otlp_exporter() -> anyhow::Result<Option<TraceExporter>> {
+    if config::env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("console") {
+        tracing::debug!("Using Console trace exporter");
+        Ok(Some(TraceExporter::Console(StdoutSpanExporter::default())))
+    } else if config::env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("memory") {
+        tracing::debug!("Using in-memory test exporter");
+        Ok(Some(TraceExporter::Memory(MEMORY_EXPORTER.lock().unwrap().clone())))
+    } else if config::env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("otlp_file") {
+        tracing::debug!("Using OTLP file trace exporter");
+        Ok(Some(TraceExporter::File(FileSpanExporter::new(JsonLinesFile::from_config(Signal::Traces)))))
+    } else if config::env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("zipkin") {
+        tracing::debug!("Using Zipkin trace exporter");
+        Ok(Some(TraceExporter::Zipkin(build_zipkin_exporter()?)))
+    } else if let Some(path) = agent::socket_path() {
+        tracing::debug!("Using agent trace exporter, socket {}", path);
+        Ok(Some(TraceExporter::Agent(AgentSpanExporter::new(path))))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Exporter for the shared span pipeline, whose batches hold the spans of several resources. OTLP
+/// batches are sent as one request by a RawSender (with the retry spool if enabled).
+fn build_pipeline_exporter() -> anyhow::Result<PipelineExporter> {
+    if let Some(exporter) = build_non_otlp_exporter()? {
+        return Ok(exporter.into());
+    }
+    let protocol = otlp::get_protocol(Signal::Traces);
+    tracing::debug!("Using {:?} trace exporter", protocol);
+    let sender = RawSender::new(Signal::Traces, protocol);
+    Ok(match Spool::from_config() {
+        Some(spool) => {
+            tracing::debug!("Using retry spool for trace exporter");
+            PipelineExporter::Spooling(SpoolingRequestSender::new(spool, sender))
+        }
+        None => PipelineExporter::Otlp(sender),
+    })
+}
+
+/// OTLP exporter for the protocol (http or gRPC), with the retry spool if enabled. Fails if the
+/// configuration (which may come from an app's .env) is invalid, eg an endpoint which is not a URI.
+fn build_otlp_exporter(protocol: OtlpProtocol) -> Result<TraceExporter, ExporterBuildError> {
+    if protocol.is_http() {
+        tracing::debug!("Using {:?} trace exporter", protocol);
+        let otlp_config = otlp::get_otlp_config(Signal::Traces, true);
+        let mut exporter_builder = OtlpSpanExporter::builder()
+            .with_http()
+            .with_protocol(protocol.http_protocol())
+            .with_headers(otlp_config.headers);
+        if let Some(endpoint) = otlp_config.endpoint {
+            exporter_builder = exporter_builder.with_endpoint(endpoint);
+        }
+        let timeout = otlp_config.timeout.unwrap_or(otlp::DEFAULT_TIMEOUT);
+        exporter_builder = exporter_builder.with_timeout(timeout);
+        match otlp::http_client(timeout, &otlp_config.tls) {
+            Ok(client) => exporter_builder = exporter_builder.with_http_client(client),
+            Err(err) => tracing::warn!("Failed to create OTLP http client, using the exporter's default: {}", err),
+        }
+        if let Some(compression) = otlp_config.compression {
+            exporter_builder = exporter_builder.with_compression(compression);
+        }
+        Ok(with_spool(exporter_builder.build()?, protocol))
+    } else {
+        tracing::debug!("Using gRPC trace exporter with tokio runtime");
+        let runtime = init_tokio_runtime();
+        let otlp_config = otlp::get_otlp_config(Signal::Traces, false);
+        let exporter = runtime.block_on(async {
+            let mut exporter_builder = OtlpSpanExporter::builder()
+                .with_tonic()
+                .with_metadata(otlp::to_metadata(&otlp_config.headers));
+            if let Some(endpoint) = otlp_config.endpoint {
+                if let Some(tls_config) = otlp::grpc_tls_config(&endpoint, &otlp_config.tls) {
+                    exporter_builder = exporter_builder.with_tls_config(tls_config);
+                }
+                exporter_builder = exporter_builder.with_endpoint(endpoint);
+            }
+            if let Some(timeout) = otlp_config.timeout {
+                exporter_builder = exporter_builder.with_timeout(timeout);
+            }
+            if let Some(compression) = otlp_config.compression {
+                exporter_builder = exporter_builder.with_compression(compression);
+            }
+            exporter_builder.build()
+        })?;
+        Ok(with_spool(exporter, OtlpProtocol::Grpc))
+    }
+}
+
+/// Zipkin exporter. The crate reads OTEL_EXPORTER_ZIPKIN_* from the process environment only, so
+/// endpoint and timeout are applied here, from the request's configuration. Fails if the endpoint
+/// is not a URI.
+fn build_zipkin_exporter() -> anyhow::Result<ZipkinExporter> {
+    let timeout = config::env::var("OTEL_EXPORTER_ZIPKIN_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(ZIPKIN_DEFAULT_TIMEOUT);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .context("Failed to create Zipkin http client")?;
+    let endpoint = config::env::var("OTEL_EXPORTER_ZIPKIN_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+        .unwrap_or_else(|| ZIPKIN_DEFAULT_ENDPOINT.to_string());
+    ZipkinExporter::builder()
+        .with_collector_endpoint(endpoint)
+        .with_http_client(client)
+        .build()
+        .context("Failed to create Zipkin exporter")
+}
+
+/// Wrap an OTLP exporter with the retry spool, if enabled
+fn with_spool(exporter: OtlpSpanExporter, protocol: OtlpProtocol) -> TraceExporter {
+    match Spool::from_config() {
+        Some(spool) => {
+            tracing::debug!("Using retry spool for trace exporter");
+            TraceExporter::Spooling(SpoolingSpanExporter::new(exporter, spool, RawSender::new(Signal::Traces, protocol)))
+        }
+        None => TraceExporter::Otlp(exporter),
+    }
 }
 
 pub fn init_once() {
     let key = get_tracer_provider_key();
+    let limits = CacheLimits::from_ini();
     let mut providers = TRACER_PROVIDERS.lock().unwrap();
-    if providers.contains_key(&key) {
+    let exists = providers.get(&key).is_some();
+    let mut evicted = providers.evict_idle(&limits, Instant::now());
+    if exists {
         tracing::debug!("tracer provider already exists for key {:?}", key);
+        lease(&mut providers, &key);
+        drop(providers);
+        provider_cache::shutdown_evicted("tracer", evicted);
         return;
     }
     tracing::debug!("creating tracer provider for key {:?}", key);
-    let use_simple_exporter = env::var("OTEL_SPAN_PROCESSOR").as_deref() == Ok("simple");
+    let use_simple_exporter = config::env::var("OTEL_SPAN_PROCESSOR").as_deref() == Ok("simple");
     tracing::debug!("SpanProcessor={}", if use_simple_exporter {"simple"} else {"batch"});
-    if env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("none") {
+    if config::env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("none") {
         tracing::debug!("Using no-op trace exporter");
-        let provider = SdkTracerProvider::builder()
-            .with_resource(Resource::builder_empty().build())
-            .with_sampler(AlwaysOff)
-            .build();
-        providers.insert(key, Arc::new(provider.clone()));
+        evicted.extend(providers.insert(key.clone(), Arc::new(build_noop_provider()), &limits));
+        lease(&mut providers, &key);
+        tracing::debug!("{} tracer providers live", providers.len());
+        drop(providers);
+        provider_cache::shutdown_evicted("tracer", evicted);
         return;
     }
 
-    let resource = Resource::builder()
-        .with_attribute(KeyValue::new("telemetry.sdk.language", "php"))
-        .with_attribute(KeyValue::new("telemetry.sdk.name", "ext-otel"))
-        .with_attribute(KeyValue::new("telemetry.sdk.version", env!("CARGO_PKG_VERSION")))
-        .with_attribute(KeyValue::new("process.runtime.name", util::get_sapi_module_name()))
-        .with_attribute(KeyValue::new("process.runtime.version", util::get_php_version()))
-        .with_attribute(KeyValue::new("process.pid", process::id().to_string()))
-        .with_attribute(KeyValue::new("host.name", hostname::get().unwrap_or_default().to_string_lossy().to_string()))
-        .build();
-
-    let mut builder = SdkTracerProvider::builder();
-    if env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("console") {
-        tracing::debug!("Using Console trace exporter");
-        let exporter = StdoutSpanExporter::default();
-        if use_simple_exporter {
-            builder = builder.with_simple_exporter(exporter);
-        } else {
-            builder = builder.with_batch_exporter(exporter);
-        }
-    } else if env::var("OTEL_TRACES_EXPORTER").as_deref() == Ok("memory") {
-        tracing::debug!("Using in-memory test exporter");
-        let exporter = MEMORY_EXPORTER.lock().unwrap().clone();
-        if use_simple_exporter {
-            builder = builder.with_simple_exporter(exporter);
-        } else {
-            builder = builder.with_batch_exporter(exporter);
-        }
+    let resource = util::build_resource();
+
+    let builder = if use_simple_exporter {
+        build_exporter().map(|exporter| SdkTracerProvider::builder().with_simple_exporter(exporter))
+    } else if shared_pipeline::is_enabled() {
+        tracing::debug!("Using shared span export pipeline");
+        shared_pipeline::get_processor(build_pipeline_exporter)
+            .map(|processor| SdkTracerProvider::builder().with_span_processor(processor))
     } else {
-        if env::var("OTEL_EXPORTER_OTLP_PROTOCOL").as_deref() == Ok("http/protobuf") {
-            tracing::debug!("Using http/protobuf trace exporter");
-            let exporter = OtlpSpanExporter::builder()
-                .with_http()
-                .with_protocol(Protocol::HttpBinary)
-                .build()
-                .expect("Failed to create OTLP http exporter");
-            if use_simple_exporter {
-                builder = builder.with_simple_exporter(exporter);
-            } else {
-                builder = builder.with_batch_exporter(exporter);
-            }
-        } else {
-            tracing::debug!("Using gRPC trace exporter with tokio runtime");
-            let runtime = init_tokio_runtime();
-            let exporter = runtime.block_on(async {
-                OtlpSpanExporter::builder()
-                    .with_tonic()
-                    .build()
-                    .expect("Failed to create OTLP grpc exporter")
-            });
-            if use_simple_exporter {
-                builder = builder.with_simple_exporter(exporter);
-            } else {
-                builder = builder.with_batch_exporter(exporter);
-            }
+        build_exporter().map(|exporter| SdkTracerProvider::builder().with_batch_exporter(exporter))
+    };
+    let provider = Arc::new(match builder {
+        Ok(builder) => builder
+            .with_resource(resource)
+            .with_sampler(get_sampler())
+            .build(),
+        Err(err) => {
+            // eg an invalid endpoint in one app's .env: don't fail the request, and don't retry every request
+            tracing::warn!("Failed to create trace exporter, using a no-op tracer provider: {:#}", err);
+            build_noop_provider()
+        }
+    });
+    evicted.extend(providers.insert(key.clone(), provider.clone(), &limits));
+    lease(&mut providers, &key);
+    tracing::debug!("{} tracer providers live", providers.len());
+    drop(providers);
+    provider_cache::shutdown_evicted("tracer", evicted);
+}
+
+/// Lease the provider until RSHUTDOWN, so that it is not evicted (and shut down) by another thread
+/// while this request's tracers may still use it.
+fn lease(providers: &mut ProviderCache<SdkTracerProvider>, key: &ProviderKey) {
+    LEASED_PROVIDERS.with(|leased| {
+        if leased.borrow_mut().insert(key.clone()) {
+            providers.acquire(key);
         }
+    });
+}
+
+/// Release the providers leased by this thread's request.
+pub fn release_leases() {
+    let leased = LEASED_PROVIDERS.with(|leased| std::mem::take(&mut *leased.borrow_mut()));
+    if leased.is_empty() {
+        return;
+    }
+    let mut providers = TRACER_PROVIDERS.lock().unwrap();
+    for key in leased {
+        providers.release(&key);
     }
-    let provider = Arc::new(builder
-        .with_resource(resource)
-        .build()
-    );
-    providers.insert(key, provider.clone());
 }
 
 pub fn get_tracer_provider() -> Arc<SdkTracerProvider> {
@@ -150,10 +384,10 @@ pub fn get_tracer_provider() -> Arc<SdkTracerProvider> {
         tracing::debug!("OpenTelemetry is disabled for this request, returning no-op tracer provider");
         return NOOP_TRACER_PROVIDER.clone();
     }
-    let providers = TRACER_PROVIDERS.lock().unwrap();
+    let mut providers = TRACER_PROVIDERS.lock().unwrap();
     let key = get_tracer_provider_key();
     if let Some(provider) = providers.get(&key) {
-        return provider.clone();
+        return provider;
     } else {
         tracing::warn!("no tracer provider initialized for key {:?}, using no-op", key);
         NOOP_TRACER_PROVIDER.clone()
@@ -164,7 +398,7 @@ pub fn force_flush() {
     let pid = process::id();
     let providers = TRACER_PROVIDERS.lock().unwrap();
     let key = get_tracer_provider_key();
-    if let Some(provider) = providers.get(&key) {
+    if let Some(provider) = providers.peek(&key) {
         tracing::info!("Flushing TracerProvider for pid {}", pid);
         match provider.force_flush() {
             Ok(_) => tracing::debug!("OpenTelemetry tracer provider flush success"),
@@ -175,19 +409,45 @@ pub fn force_flush() {
     }
 }
 
+/// Flush every provider of this process (not only the current request's), and the shared span
+/// export pipelines, eg before forking.
+pub fn force_flush_all() {
+    let pid = process::id();
+    let providers = TRACER_PROVIDERS.lock().unwrap().providers_where(|(k_pid, _)| *k_pid == pid);
+    tracing::info!("Flushing {} tracer providers for pid {}", providers.len(), pid);
+    for provider in providers {
+        if let Err(err) = provider.force_flush() {
+            tracing::warn!("Failed to flush OpenTelemetry tracer provider: {:?}", err);
+        }
+    }
+    shared_pipeline::flush_all();
+}
+
+/// In a forked child, discard providers inherited from the parent without shutting them down (their
+/// export threads only exist in the parent).
+pub fn forget_inherited() {
+    let pid = process::id();
+    let mut providers = TRACER_PROVIDERS.lock().unwrap();
+    for (key, provider) in providers.remove_where(|(k_pid, _)| *k_pid != pid) {
+        tracing::debug!("Forgetting inherited TracerProvider for key {:?}", key);
+        std::mem::forget(provider);
+    }
+    LEASED_PROVIDERS.with(|leased| leased.borrow_mut().clear());
+}
+
+/// Number of cached tracer providers, for phpinfo()
+pub fn provider_count() -> usize {
+    TRACER_PROVIDERS.lock().map(|providers| providers.len()).unwrap_or(0)
+}
+
 pub fn shutdown() {
     let pid = process::id();
     let mut providers = TRACER_PROVIDERS.lock().unwrap();
-    let keys_to_remove: Vec<_> = providers
-        .keys()
-        .filter(|(k_pid, _)| *k_pid == pid)
-        .cloned()
-        .collect();
-    if !keys_to_remove.is_empty() {
+    let removed = providers.remove_where(|(k_pid, _)| *k_pid == pid);
+    if !removed.is_empty() {
         tracing::info!("Shutting down all TracerProviders for pid {}", pid);
-        for key in keys_to_remove {
+        for (key, _provider) in removed {
             tracing::debug!("Shutting down TracerProvider for key {:?}", key);
-            providers.remove(&key);
         }
     } else {
         tracing::info!("no tracer providers to shutdown for pid {}", pid);
@@ -237,9 +497,9 @@ pub fn make_tracer_provider_class(
             }
             let scope = scope_builder.build();
 
-            let tracer = provider.tracer_with_scope(scope);
+            let tracer = provider.tracer_with_scope(scope.clone());
             let mut object = tracer_class.init_object()?;
-            *object.as_mut_state() = Some(tracer);
+            *object.as_mut_state() = Some(TracerState::new(tracer, scope));
             Ok::<_, phper::Error>(object)
         })
         .argument(Argument::new("name").with_type_hint(ArgumentTypeHint::String))
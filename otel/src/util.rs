@@ -9,7 +9,9 @@ use opentelemetry::{
     StringValue,
     Value,
 };
-use std::ffi::CStr;
+use opentelemetry_sdk::Resource;
+use std::{ffi::CStr, process};
+use crate::{config, request};
 
 /// Convert a ZVal to a single KeyValue pair based on its type.
 pub fn zval_to_key_value(key: &str, value: &ZVal) -> Option<KeyValue> {
@@ -71,6 +73,33 @@ pub fn get_php_version() -> String {
     php_version
 }
 
+/// Build the resource shared by tracer and logger providers. OTEL_SERVICE_NAME and
+/// OTEL_RESOURCE_ATTRIBUTES are applied from the request-scoped environment, since with ZTS they
+/// are not visible to the SDK's environment resource detectors.
+pub fn build_resource() -> Resource {
+    let mut builder = Resource::builder()
+        .with_attribute(KeyValue::new("telemetry.sdk.language", "php"))
+        .with_attribute(KeyValue::new("telemetry.sdk.name", "ext-otel"))
+        .with_attribute(KeyValue::new("telemetry.sdk.version", env!("CARGO_PKG_VERSION")))
+        .with_attribute(KeyValue::new("process.runtime.name", get_sapi_module_name()))
+        .with_attribute(KeyValue::new("process.runtime.version", get_php_version()))
+        .with_attribute(KeyValue::new("process.pid", process::id().to_string()))
+        .with_attribute(KeyValue::new("host.name", hostname::get().unwrap_or_default().to_string_lossy().to_string()));
+    if let Ok(resource_attrs) = config::env::var("OTEL_RESOURCE_ATTRIBUTES") {
+        builder = builder.with_attributes(
+            request::parse_resource_attributes(&resource_attrs)
+                .into_iter()
+                .map(|(k, v)| KeyValue::new(k, v)),
+        );
+    }
+    if let Ok(service_name) = config::env::var("OTEL_SERVICE_NAME") {
+        if !service_name.is_empty() {
+            builder = builder.with_service_name(service_name);
+        }
+    }
+    builder.build()
+}
+
 fn zval_to_vec(key: &str, value: &ZVal) -> Option<KeyValue> {
     let array = value.as_z_arr()?;
 
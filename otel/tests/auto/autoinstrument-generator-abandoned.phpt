@@ -0,0 +1,46 @@
+--TEST--
+Autoinstrument a generator which is destroyed before completing, then another generator
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_VERSION_ID < 80000) {
+    die("skip requires PHP 8.0+ (observer API)");
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.cli.enabled=1
+otel.log.level="warn"
+--FILE--
+<?php
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+function demoGenerator() {
+    yield 1;
+    yield 2;
+}
+
+$generator = demoGenerator();
+foreach ($generator as $value) {
+    var_dump($value);
+    break;
+}
+unset($generator);
+
+// the new generator may reuse the destroyed one's memory, but is not mistaken for it
+$before = count(Memory::getSpans());
+foreach (demoGenerator() as $value) {
+    var_dump($value);
+}
+var_dump(array_column(array_slice(Memory::getSpans(), $before), 'name'));
+?>
+--EXPECT--
+int(1)
+int(1)
+int(2)
+array(1) {
+  [0]=>
+  string(13) "demoGenerator"
+}
\ No newline at end of file
@@ -0,0 +1,54 @@
+--TEST--
+Autoinstrument a generator: one span covering all yields, and not active while suspended
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_VERSION_ID < 80000) {
+    die("skip requires PHP 8.0+ (observer API)");
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.cli.enabled=1
+otel.log.level="warn"
+--FILE--
+<?php
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+function demoFunction() {
+    var_dump("demo_function");
+}
+
+function demoGenerator() {
+    yield 1;
+    demoFunction();
+    yield 2;
+    return 3;
+}
+
+$generator = demoGenerator();
+foreach ($generator as $value) {
+    var_dump($value);
+    demoFunction();
+}
+var_dump($generator->getReturn());
+
+$spans = Memory::getSpans();
+assert(count($spans) === 4);
+$generatorSpan = $spans[3];
+assert($generatorSpan['name'] === 'demoGenerator');
+// called between yields: child of the generator span
+assert($spans[1]['parent_span_id'] === $generatorSpan['span_context']['span_id']);
+// called by the caller while the generator is suspended: not a child of the generator span
+assert($spans[0]['parent_span_id'] !== $generatorSpan['span_context']['span_id']);
+assert($spans[2]['parent_span_id'] !== $generatorSpan['span_context']['span_id']);
+?>
+--EXPECT--
+int(1)
+string(13) "demo_function"
+string(13) "demo_function"
+int(2)
+string(13) "demo_function"
+int(3)
@@ -0,0 +1,36 @@
+--TEST--
+Process execution spans, by default without the command line or trace context propagation
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_VERSION_ID < 80200) {
+    die('skip: requires PHP 8.2+ to observe internal functions');
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.log.level="warn"
+otel.log.file="/dev/stderr"
+otel.cli.enabled=1
+--FILE--
+<?php
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+var_dump(exec('echo "[$TRACEPARENT]" secret=hunter2', $output, $result_code));
+var_dump($result_code);
+
+$span = Memory::getSpans()[0];
+var_dump($span['name']);
+var_dump($span['attributes']['process.command']);
+var_dump($span['attributes']['process.exit.code']);
+var_dump(array_key_exists('process.command_line', $span['attributes']));
+?>
+--EXPECT--
+string(17) "[] secret=hunter2"
+int(0)
+string(9) "exec echo"
+string(4) "echo"
+int(0)
+bool(false)
\ No newline at end of file
@@ -0,0 +1,75 @@
+--TEST--
+Process execution spans, with trace context passed to the child's environment
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_VERSION_ID < 80200) {
+    die('skip: requires PHP 8.2+ to observe internal functions');
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+OTEL_PHP_EXEC_CAPTURE_COMMAND_LINE=true
+OTEL_PHP_EXEC_PROPAGATE_CONTEXT=true
+--INI--
+otel.log.level="warn"
+otel.log.file="/dev/stderr"
+otel.cli.enabled=1
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+$span = Globals::tracerProvider()->getTracer('my_tracer')->spanBuilder('root')->startSpan();
+$scope = $span->activate();
+
+$output = [];
+exec('echo $TRACEPARENT; exit 3', $output, $result_code);
+var_dump($output[0] === sprintf('00-%s-%s-01', $span->getContext()->getTraceId(), Memory::getSpans()[0]['span_context']['span_id']));
+var_dump($result_code);
+
+$process = proc_open(['sh', '-c', 'echo $TRACEPARENT'], [1 => ['pipe', 'w']], $pipes, null, ['PATH' => getenv('PATH')]);
+var_dump(strpos(stream_get_contents($pipes[1]), '00-' . $span->getContext()->getTraceId()) === 0);
+fclose($pipes[1]);
+proc_close($process);
+
+$scope->detach();
+$span->end();
+
+$spans = Memory::getSpans();
+foreach ([$spans[0], $spans[1]] as $child) {
+    var_dump($child['name']);
+    var_dump($child['parent_span_id'] === $span->getContext()->getSpanId());
+    var_dump(array_intersect_key($child['attributes'], array_flip(['process.command', 'process.command_line', 'process.command_args', 'process.exit.code'])));
+}
+?>
+--EXPECT--
+bool(true)
+int(3)
+bool(true)
+string(9) "exec echo"
+bool(true)
+array(3) {
+  ["process.command"]=>
+  string(4) "echo"
+  ["process.command_line"]=>
+  string(25) "echo $TRACEPARENT; exit 3"
+  ["process.exit.code"]=>
+  int(3)
+}
+string(12) "proc_open sh"
+bool(true)
+array(2) {
+  ["process.command"]=>
+  string(2) "sh"
+  ["process.command_args"]=>
+  array(3) {
+    [0]=>
+    string(2) "sh"
+    [1]=>
+    string(2) "-c"
+    [2]=>
+    string(17) "echo $TRACEPARENT"
+  }
+}
\ No newline at end of file
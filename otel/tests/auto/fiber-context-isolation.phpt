@@ -0,0 +1,51 @@
+--TEST--
+Active span is swapped when switching between fibers
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_VERSION_ID < 80100) {
+    die("skip requires PHP 8.1+ (fibers)");
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.cli.enabled=1
+otel.log.level="warn"
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\Span;
+
+$tracer = Globals::tracerProvider()->getTracer('my_tracer');
+$main = $tracer->spanBuilder('main')->startSpan();
+$mainScope = $main->activate();
+$mainSpanId = $main->getContext()->getSpanId();
+
+$fiber = new Fiber(function () use ($tracer, $mainSpanId): void {
+    // a new fiber inherits the context it was started from
+    var_dump(Span::getCurrent()->getContext()->getSpanId() === $mainSpanId);
+    $inner = $tracer->spanBuilder('inner')->startSpan();
+    $innerScope = $inner->activate();
+    $innerSpanId = $inner->getContext()->getSpanId();
+    Fiber::suspend();
+    var_dump(Span::getCurrent()->getContext()->getSpanId() === $innerSpanId);
+    $innerScope->detach();
+    $inner->end();
+});
+
+$fiber->start();
+// the fiber's active span does not leak into main
+var_dump(Span::getCurrent()->getContext()->getSpanId() === $mainSpanId);
+$fiber->resume();
+var_dump(Span::getCurrent()->getContext()->getSpanId() === $mainSpanId);
+
+$mainScope->detach();
+$main->end();
+?>
+--EXPECT--
+bool(true)
+bool(true)
+bool(true)
+bool(true)
@@ -61,6 +61,12 @@ Span #0
 	Attributes:
 		 ->  url.full: String(Owned("/"))
 		 ->  http.request.method: String(Owned("GET"))
+		 ->  url.scheme: String(Owned("http"))
+		 ->  url.path: String(Owned("/"))
+		 ->  server.address: String(Owned("127.0.0.1"))
+		 ->  server.port: I64(8080)
+		 ->  client.address: String(Owned("127.0.0.1"))
+		 ->  network.protocol.version: String(Owned("%s"))
 		 ->  php.framework.name: String(Static("laminas"))
 		 ->  php.framework.controller.name: String(Owned("Application\\Controller\\IndexController"))
 		 ->  php.framework.action.name: String(Owned("index"))
@@ -68,5 +68,11 @@ Span #0
 	Attributes:
 		 ->  url.full: String(Owned("/does-not-exist"))
 		 ->  http.request.method: String(Owned("GET"))
+		 ->  url.scheme: String(Owned("http"))
+		 ->  url.path: String(Owned("/does-not-exist"))
+		 ->  server.address: String(Owned("127.0.0.1"))
+		 ->  server.port: I64(8080)
+		 ->  client.address: String(Owned("127.0.0.1"))
+		 ->  network.protocol.version: String(Owned("%s"))
 		 ->  php.framework.name: String(Static("laminas"))
 		 ->  http.response.status_code: I64(404)%A
\ No newline at end of file
@@ -0,0 +1,28 @@
+--TEST--
+A panicking plugin hook is caught, and the plugin disabled after reaching the failure threshold
+--EXTENSIONS--
+otel
+--ENV--
+OTEL_TRACES_EXPORTER=none
+--INI--
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+otel.cli.enabled=1
+otel.auto.plugin_failure_threshold=2
+--FILE--
+<?php
+function demoPanic() {
+    var_dump("demo_panic");
+}
+
+demoPanic();
+demoPanic();
+demoPanic();
+?>
+--EXPECTF--
+%A[%s] [ERROR] [pid=%d] [ThreadId(%d)] otel::auto::circuit_breaker: event src/auto/circuit_breaker.rs:%d message=plugin test hook panicked (1 failures): DemoPanicHandler: pre_callback panicked
+%Astring(10) "demo_panic"
+%A[%s] [ERROR] [pid=%d] [ThreadId(%d)] otel::auto::circuit_breaker: event src/auto/circuit_breaker.rs:%d message=plugin test hook panicked (2 failures): DemoPanicHandler: pre_callback panicked
+[%s] [ERROR] [pid=%d] [ThreadId(%d)] otel::auto::circuit_breaker: event src/auto/circuit_breaker.rs:%d message=plugin test disabled after 2 failures
+string(10) "demo_panic"
+string(10) "demo_panic"
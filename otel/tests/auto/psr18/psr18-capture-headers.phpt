@@ -0,0 +1,70 @@
+--TEST--
+Capture psr-18 request and response headers, with sanitization
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_VERSION_ID < 70200) {
+    // ignored as psr18 not installable on PHP < 7.2
+    die("skip requires PHP 7.2+");
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+OTEL_INSTRUMENTATION_HTTP_CLIENT_CAPTURE_REQUEST_HEADERS=x-foo,Authorization,x-missing
+OTEL_INSTRUMENTATION_HTTP_CLIENT_CAPTURE_RESPONSE_HEADERS=content-type
+--INI--
+otel.log.level="warn"
+otel.log.file="/dev/stderr"
+otel.cli.enabled=1
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use Psr\Http\Client\ClientInterface;
+use Psr\Http\Message\RequestInterface;
+use Psr\Http\Message\ResponseInterface;
+use Nyholm\Psr7\Request;
+use Nyholm\Psr7\Response;
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+require __DIR__ . '/vendor/autoload.php';
+
+class MockHttpClient implements ClientInterface
+{
+    public function sendRequest(RequestInterface $request): ResponseInterface
+    {
+        return new Response(200, ['Content-Type' => 'text/plain'], "Mock response body");
+    }
+}
+
+$request = new Request('GET', 'http://example.com/', ['x-foo' => ['bar', 'baz'], 'Authorization' => 'Bearer secret']);
+$client = new MockHttpClient();
+
+$span = Globals::tracerProvider()->getTracer('my_tracer', '0.1', 'schema.url')->spanBuilder('root')->startSpan();
+$scope = $span->activate();
+$client->sendRequest($request);
+$span->end();
+$scope->detach();
+
+$attributes = Memory::getSpans()[0]['attributes'];
+var_dump($attributes['http.request.header.x-foo']);
+var_dump($attributes['http.request.header.authorization']);
+var_dump(array_key_exists('http.request.header.x-missing', $attributes));
+var_dump($attributes['http.response.header.content-type']);
+?>
+--EXPECT--
+array(2) {
+  [0]=>
+  string(3) "bar"
+  [1]=>
+  string(3) "baz"
+}
+array(1) {
+  [0]=>
+  string(10) "[REDACTED]"
+}
+bool(false)
+array(1) {
+  [0]=>
+  string(10) "text/plain"
+}
\ No newline at end of file
@@ -37,6 +37,12 @@ Span #0
 	Attributes:
 		 ->  url.full: String(Owned("/"))
 		 ->  http.request.method: String(Owned("GET"))
+		 ->  url.scheme: String(Owned("http"))
+		 ->  url.path: String(Owned("/"))
+		 ->  server.address: String(Owned("127.0.0.1"))
+		 ->  server.port: I64(8080)
+		 ->  client.address: String(Owned("127.0.0.1"))
+		 ->  network.protocol.version: String(Owned("%s"))
 		 ->  php.framework.name: String(Static("zf1"))
 		 ->  php.framework.module.name: String(Owned("default"))
 		 ->  php.framework.controller.name: String(Owned("index"))
@@ -0,0 +1,35 @@
+--TEST--
+Capture HTTP server request and response headers, with sanitization
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+        "header" => "X-Request-Id: abc123\r\nCookie: session=secret\r\n",
+    ]
+];
+
+run_server('http/server-headers.php', $options, '', 'OTEL_INSTRUMENTATION_HTTP_SERVER_CAPTURE_REQUEST_HEADERS=x-request-id,cookie OTEL_INSTRUMENTATION_HTTP_SERVER_CAPTURE_RESPONSE_HEADERS=set-cookie,x-powered-by');
+?>
+--EXPECTF--
+==== Response ====
+string(3) "GET"
+==== Server Output ====%A
+	Attributes:
+%A
+		 ->  http.request.header.x-request-id: Array(String([Owned("abc123")]))
+		 ->  http.request.header.cookie: Array(String([Static("[REDACTED]")]))
+		 ->  http.response.status_code: I64(200)
+		 ->  http.response.body.size: I64(16)
+		 ->  http.response.header.set-cookie: Array(String([Static("[REDACTED]")]))
+		 ->  http.response.header.x-powered-by: Array(String([Owned("otel-test")]))%A
\ No newline at end of file
@@ -0,0 +1,31 @@
+--TEST--
+Test request exclusion by user agent
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+        "user_agent" => "ELB-HealthChecker/2.0",
+    ]
+];
+
+run_server('http/server-get.php', $options, '/status', "OTEL_PHP_EXCLUDED_METHODS=OPTIONS,HEAD OTEL_PHP_EXCLUDED_USER_AGENTS='elb-healthchecker|uptimerobot'", 'trace');
+?>
+--EXPECTF--
+==== Response ====
+string(3) "GET"
+string(7) "/status"
+==== Server Output ====
+%A
+%sexcluded request, user agent rule matched%s
+%A
\ No newline at end of file
@@ -0,0 +1,33 @@
+--TEST--
+Test traceparent meta tag injection into HTML responses
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+        "header" => "traceparent: 00-e77388f01a826e2de7afdcd1eefc034e-d6ba64af4fa59b65-01\r\n"
+    ]
+];
+
+run_server('http/server-html.php', $options, '/', 'OTEL_PHP_INJECT_HTML_TRACEPARENT=true');
+?>
+--EXPECTF--
+==== Response ====
+<!DOCTYPE html>
+<html>
+<head><meta name="traceparent" content="00-e77388f01a826e2de7afdcd1eefc034e-%x-01">
+<title>test</title>
+</head>
+<body></body>
+</html>
+==== Server Output ====%A
\ No newline at end of file
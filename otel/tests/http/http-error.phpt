@@ -37,4 +37,10 @@ Span #0
 	Attributes:
 		 ->  url.full: String(Owned("/"))
 		 ->  http.request.method: String(Owned("GET"))
+		 ->  url.scheme: String(Owned("http"))
+		 ->  url.path: String(Owned("/"))
+		 ->  server.address: String(Owned("127.0.0.1"))
+		 ->  server.port: I64(8080)
+		 ->  client.address: String(Owned("127.0.0.1"))
+		 ->  network.protocol.version: String(Owned("%s"))
 		 ->  http.response.status_code: I64(500)%A
\ No newline at end of file
@@ -42,4 +42,11 @@ Span #0
 	Attributes:
 		 ->  url.full: String(Owned("/"))
 		 ->  http.request.method: String(Owned("POST"))
-		 ->  http.response.status_code: I64(201)%A
\ No newline at end of file
+		 ->  url.scheme: String(Owned("http"))
+		 ->  url.path: String(Owned("/"))
+		 ->  server.address: String(Owned("127.0.0.1"))
+		 ->  server.port: I64(8080)
+		 ->  client.address: String(Owned("127.0.0.1"))
+		 ->  network.protocol.version: String(Owned("%s"))
+		 ->  http.response.status_code: I64(201)
+		 ->  http.response.body.size: I64(15)%A
\ No newline at end of file
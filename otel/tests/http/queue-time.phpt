@@ -0,0 +1,44 @@
+--TEST--
+Test request queue time from X-Request-Start
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+        "header" => sprintf("X-Request-Start: t=%d\r\n", (int) (microtime(true) * 1000) - 250),
+    ]
+];
+
+run_server('http/server-get.php', $options);
+?>
+--EXPECTF--
+==== Response ====
+string(3) "GET"
+string(1) "/"
+==== Server Output ====%A
+Spans
+Resource
+%A
+Span #0
+	Instrumentation Scope
+%A
+	Name         : GET
+%A
+	Kind         : Server
+%A
+	Attributes:
+		 ->  url.full: String(Owned("/"))
+		 ->  http.request.method: String(Owned("GET"))
+%A
+		 ->  http.server.request.queue_time: F64(%f)
+		 ->  http.response.status_code: I64(200)%A
\ No newline at end of file
@@ -0,0 +1,43 @@
+--TEST--
+Test root span naming from route rules
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+    ]
+];
+
+run_server('http/server-get.php', $options, '/invoice/42', "OTEL_PHP_HTTP_ROUTES='^/users/\\d+\$ => /users/{id}; ^/invoice/\\d+\$ => /invoice/{id}'");
+?>
+--EXPECTF--
+==== Response ====
+string(3) "GET"
+string(11) "/invoice/42"
+==== Server Output ====%A
+Spans
+Resource
+%A
+Span #0
+	Instrumentation Scope
+%A
+	Name         : GET /invoice/{id}
+%A
+	Kind         : Server
+%A
+	Attributes:
+		 ->  url.full: String(Owned("/invoice/42"))
+		 ->  http.request.method: String(Owned("GET"))
+%A
+		 ->  http.route: String(Owned("/invoice/{id}"))
+		 ->  http.response.status_code: I64(200)%A
\ No newline at end of file
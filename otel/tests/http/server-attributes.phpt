@@ -0,0 +1,52 @@
+--TEST--
+Test HTTP server attributes, with client address from a trusted proxy
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+        "user_agent" => "otel-test/1.0",
+        "header" => "X-Forwarded-For: 6.6.6.6, 203.0.113.7, 10.0.0.1\r\nX-Forwarded-Proto: https\r\n",
+    ]
+];
+
+run_server('http/server-get.php', $options, '/users?id=1', 'OTEL_PHP_TRUSTED_PROXIES=127.0.0.1,10.0.0.0/8');
+?>
+--EXPECTF--
+==== Response ====
+string(3) "GET"
+string(11) "/users?id=1"
+==== Server Output ====%A
+Spans
+Resource
+%A
+Span #0
+	Instrumentation Scope
+%A
+	Name         : GET
+%A
+	Kind         : Server
+%A
+	Attributes:
+		 ->  url.full: String(Owned("/users?id=1"))
+		 ->  http.request.method: String(Owned("GET"))
+		 ->  url.scheme: String(Owned("https"))
+		 ->  url.path: String(Owned("/users"))
+		 ->  url.query: String(Owned("id=1"))
+		 ->  server.address: String(Owned("127.0.0.1"))
+		 ->  server.port: I64(8080)
+		 ->  client.address: String(Owned("203.0.113.7"))
+		 ->  user_agent.original: String(Owned("otel-test/1.0"))
+		 ->  network.protocol.version: String(Owned("%s"))
+		 ->  http.response.status_code: I64(200)
+		 ->  http.response.body.size: I64(41)%A
\ No newline at end of file
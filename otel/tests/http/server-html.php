@@ -0,0 +1,9 @@
+<?php
+?>
+<!DOCTYPE html>
+<html>
+<head>
+<title>test</title>
+</head>
+<body></body>
+</html>
@@ -0,0 +1,32 @@
+--TEST--
+Test traceresponse and Server-Timing response headers
+--SKIPIF--
+<?php
+if (PHP_SAPI !== 'cli') {
+    die('skip: Not running in CLI mode');
+}
+?>
+--EXTENSIONS--
+otel
+--FILE--
+<?php
+include dirname(__DIR__) . '/run-server.php';
+
+$options = [
+    "http" => [
+        "method" => "GET",
+        "header" => "traceparent: 00-e77388f01a826e2de7afdcd1eefc034e-d6ba64af4fa59b65-01\r\n"
+    ]
+];
+
+run_server('http/server-get.php', $options, '/', 'OTEL_PHP_TRACE_RESPONSE_HEADERS=traceresponse,server-timing', 'debug');
+?>
+--EXPECTF--
+==== Response ====
+string(3) "GET"
+string(1) "/"
+==== Server Output ====
+%A
+%sadding response header traceresponse: 00-e77388f01a826e2de7afdcd1eefc034e-%x-01%s
+%sadding response header Server-Timing: traceparent;desc="00-e77388f01a826e2de7afdcd1eefc034e-%x-01"%s
+%A
\ No newline at end of file
@@ -0,0 +1,28 @@
+--TEST--
+Export a log: http/json, from per-signal protocol
+--EXTENSIONS--
+otel
+--INI--
+otel.cli.enabled=On
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+--ENV--
+OTEL_EXPORTER_OTLP_ENDPOINT=http://collector:4318
+OTEL_EXPORTER_OTLP_PROTOCOL=grpc
+OTEL_EXPORTER_OTLP_LOGS_PROTOCOL=http/json
+OTEL_EXPORTER_OTLP_TIMEOUT=1500
+OTEL_SERVICE_NAME=test-http-json
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Logs\LogRecord;
+
+$logger = Globals::loggerProvider()->getLogger("my_logger", '0.1', 'schema.url', ['one' => 1]);
+$record = new LogRecord('test');
+$record->setSeverityNumber(9); //info
+$logger->emit($record);
+
+var_dump('done');
+?>
+--EXPECT--
+string(4) "done"
\ No newline at end of file
@@ -0,0 +1,30 @@
+--TEST--
+Export a span: grpc, gzip compressed, insecure endpoint without scheme
+--EXTENSIONS--
+otel
+--INI--
+otel.cli.enabled=On
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+--ENV--
+OTEL_EXPORTER_OTLP_ENDPOINT=collector:4317
+OTEL_EXPORTER_OTLP_INSECURE=true
+OTEL_EXPORTER_OTLP_PROTOCOL=grpc
+OTEL_EXPORTER_OTLP_COMPRESSION=gzip
+OTEL_SERVICE_NAME=test-grpc-gzip
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\StatusCode;
+
+Globals::tracerProvider()
+    ->getTracer('my_tracer', '0.1', 'schema.url')
+    ->spanBuilder('root')
+    ->setAttribute('exporter', 'grpc')
+    ->startSpan()
+    ->setStatus(StatusCode::STATUS_ERROR, 'kaboom')
+    ->end();
+var_dump('done');
+?>
+--EXPECT--
+string(4) "done"
\ No newline at end of file
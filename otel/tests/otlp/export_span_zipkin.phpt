@@ -0,0 +1,29 @@
+--TEST--
+Export a span: zipkin
+--EXTENSIONS--
+otel
+--INI--
+otel.cli.enabled=On
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+--ENV--
+OTEL_TRACES_EXPORTER=zipkin
+OTEL_EXPORTER_ZIPKIN_ENDPOINT=http://collector:9411/api/v2/spans
+OTEL_EXPORTER_ZIPKIN_TIMEOUT=1500
+OTEL_SERVICE_NAME=test-zipkin
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\StatusCode;
+
+Globals::tracerProvider()
+    ->getTracer('my_tracer', '0.1', 'schema.url')
+    ->spanBuilder('root')
+    ->setAttribute('exporter', 'zipkin')
+    ->startSpan()
+    ->setStatus(StatusCode::STATUS_ERROR, 'kaboom')
+    ->end();
+var_dump('done');
+?>
+--EXPECT--
+string(4) "done"
\ No newline at end of file
@@ -0,0 +1,30 @@
+--TEST--
+Any OTEL_* variable can be set from .env
+--EXTENSIONS--
+otel
+--INI--
+otel.cli.enabled=On
+otel.env.dotenv.enabled=On
+otel.log.level=debug
+otel.log.file="/dev/stdout"
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+OTEL_TRACES_SAMPLER=always_on
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+Globals::tracerProvider()->getTracer('my_tracer')->spanBuilder('root')->startSpan()->end();
+var_dump(Memory::count());
+var_dump(getenv('APP_SECRET'));
+?>
+--EXPECTF--
+%A
+[%s] [DEBUG] [pid=%d] [ThreadId(%d)] %s message=Set environment variable from .env OTEL_EXPORTER_OTLP_HEADERS=<redacted>
+%A
+[%s] [DEBUG] [pid=%d] [ThreadId(%d)] otel::trace::tracer_provider: event src/trace/tracer_provider.rs:%d message=creating tracer provider for key (%d, "from-dotenv::%s")
+%A
+int(0)
+bool(false)
+%A
@@ -0,0 +1,30 @@
+--TEST--
+Invalid OTLP endpoint falls back to no-op providers
+--DESCRIPTION--
+An endpoint which is not a URI (eg from an app's .env) fails exporter creation. The request should
+still run, with a warning logged and nothing exported.
+--EXTENSIONS--
+otel
+--INI--
+otel.log.level="warn"
+otel.log.file="/dev/stdout"
+otel.cli.enabled=1
+--ENV--
+OTEL_EXPORTER_OTLP_ENDPOINT=http://not a uri:4318
+OTEL_EXPORTER_OTLP_PROTOCOL=http/protobuf
+OTEL_SPAN_PROCESSOR=simple
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+
+Globals::tracerProvider()
+    ->getTracer('my_tracer', '0.1', 'schema.url')
+    ->spanBuilder('root')
+    ->startSpan()
+    ->end();
+echo 'done';
+?>
+--EXPECTF--
+%A[WARN]%smessage=Failed to create trace exporter, using a no-op tracer provider: %A
+%A[WARN]%smessage=Failed to create OTLP log exporter, logs will not be exported: %A
+done
\ No newline at end of file
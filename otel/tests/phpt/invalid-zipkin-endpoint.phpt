@@ -0,0 +1,29 @@
+--TEST--
+Invalid Zipkin endpoint falls back to a no-op tracer provider
+--DESCRIPTION--
+A Zipkin endpoint which is not a URI fails exporter creation. The request should still run, with a
+warning logged and nothing exported.
+--EXTENSIONS--
+otel
+--INI--
+otel.log.level="warn"
+otel.log.file="/dev/stdout"
+otel.cli.enabled=1
+--ENV--
+OTEL_TRACES_EXPORTER=zipkin
+OTEL_EXPORTER_ZIPKIN_ENDPOINT=not a uri
+OTEL_LOGS_EXPORTER=none
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+
+Globals::tracerProvider()
+    ->getTracer('my_tracer', '0.1', 'schema.url')
+    ->spanBuilder('root')
+    ->startSpan()
+    ->end();
+echo 'done';
+?>
+--EXPECTF--
+%A[WARN]%smessage=Failed to create trace exporter, using a no-op tracer provider: Failed to create Zipkin exporter: %A
+done
\ No newline at end of file
@@ -14,12 +14,23 @@ version => %s
 %A
 
 Directive => Local Value => Master Value
+otel.agent.socket => no value => no value
 otel.auto.disabled_plugins => no value => no value
 otel.auto.enabled => 1 => 1
+otel.auto.plugin_failure_threshold => 5 => 5
 otel.cli.create_root_span => 0 => 0
 otel.cli.enabled => 0 => 0
+otel.cli.flush_interval => 10 => 10
+otel.env.dotenv.cache_ttl => 60 => 60
 otel.env.dotenv.enabled => 0 => 0
 otel.env.set_from_server => 0 => 0
+otel.http.route_script_fallback => 0 => 0
+otel.http.routes => no value => no value
 otel.log.file => %s => %s
 otel.log.level => error => error
+otel.provider_cache.idle_timeout => 3600 => 3600
+otel.provider_cache.max_size => 16 => 16
+otel.spool.dir => no value => no value
+otel.spool.max_size => 100 => 100
+otel.traces.shared_pipeline => 1 => 1
 %A
\ No newline at end of file
@@ -0,0 +1,49 @@
+--TEST--
+fastcgi_finish_request ends the root span, and continues in a post-response span
+--DESCRIPTION--
+The CLI has no fastcgi_finish_request, so test builds provide one, which is wrapped like php-fpm's.
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (!function_exists('fastcgi_finish_request')) {
+    die('skip: requires a build with the test feature');
+}
+--ENV--
+OTEL_TRACES_EXPORTER=console
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+otel.cli.create_root_span="On"
+otel.cli.enabled=1
+--FILE--
+<?php
+echo "before" . PHP_EOL;
+var_dump(fastcgi_finish_request());
+echo "after" . PHP_EOL;
+?>
+--EXPECTF--
+before
+Spans
+Resource
+%A
+Span #0
+	Instrumentation Scope
+		Name         : "php:rinit"
+
+	Name         : finish-request.php
+%A
+bool(true)
+after
+Spans
+Resource
+%A
+Span #0
+	Instrumentation Scope
+		Name         : "php:rinit"
+
+	Name         : post-response
+%A
+	Kind         : Internal
+%A
\ No newline at end of file
@@ -0,0 +1,55 @@
+--TEST--
+Forked child gets its own root span, parented to the parent's current span
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (!extension_loaded('pcntl')) {
+    die('skip: pcntl extension required');
+}
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+otel.cli.create_root_span="On"
+otel.cli.enabled=1
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\LocalRootSpan;
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+$tracer = Globals::tracerProvider()->getTracer('test');
+$parentRoot = LocalRootSpan::current()->getContext();
+$span = $tracer->spanBuilder('parent-work')->startSpan();
+$scope = $span->activate();
+
+$pid = pcntl_fork();
+if ($pid === 0) {
+    $root = LocalRootSpan::current()->getContext();
+    var_dump($root->getTraceId() === $parentRoot->getTraceId());
+    var_dump($root->getSpanId() !== $parentRoot->getSpanId());
+    $tracer->spanBuilder('child-work')->startSpan()->end();
+    $spans = Memory::getSpans();
+    var_dump(count($spans));
+    var_dump($spans[0]['name']);
+    var_dump($spans[0]['parent_span_id'] === $root->getSpanId());
+    exit(0);
+}
+pcntl_waitpid($pid, $status);
+$scope->detach();
+$span->end();
+$spans = Memory::getSpans();
+var_dump(count($spans));
+var_dump($spans[0]['name']);
+?>
+--EXPECT--
+bool(true)
+bool(true)
+int(1)
+string(10) "child-work"
+bool(true)
+int(1)
+string(11) "parent-work"
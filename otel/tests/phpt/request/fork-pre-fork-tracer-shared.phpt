@@ -0,0 +1,42 @@
+--TEST--
+Tracer created before forking exports the child's spans (shared span pipeline)
+--DESCRIPTION--
+The tracer is bound to the parent's provider, whose export thread does not exist in the child. It must
+be rebound to the child's provider, otherwise the child's spans are lost.
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (!extension_loaded('pcntl')) {
+    die('skip: pcntl extension required');
+}
+--ENV--
+OTEL_TRACES_EXPORTER=otlp_file
+OTEL_EXPORTER_OTLP_FILE_PATH=/tmp/otel-fork-pre-fork-tracer-shared
+--INI--
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+otel.cli.enabled=1
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+
+$dir = getenv('OTEL_EXPORTER_OTLP_FILE_PATH');
+$tracer = Globals::tracerProvider()->getTracer('test');
+
+$pid = pcntl_fork();
+if ($pid === 0) {
+    $tracer->spanBuilder('child-work')->startSpan()->end();
+    exit(0);
+}
+pcntl_waitpid($pid, $status);
+$exported = @file_get_contents("$dir/traces-$pid.jsonl");
+var_dump(strpos((string) $exported, '"child-work"') !== false);
+?>
+--CLEAN--
+<?php
+array_map('unlink', glob(getenv('OTEL_EXPORTER_OTLP_FILE_PATH') . '/*') ?: []);
+@rmdir(getenv('OTEL_EXPORTER_OTLP_FILE_PATH'));
+?>
+--EXPECT--
+bool(true)
\ No newline at end of file
@@ -0,0 +1,47 @@
+--TEST--
+Each request handled by a FrankenPHP worker gets its own environment and root span
+--DESCRIPTION--
+frankenphp_handle_request wraps its callback in RequestHandler, which is invoked directly here.
+--EXTENSIONS--
+otel
+--SKIPIF--
+<?php
+if (PHP_ZTS) {
+    die('skip: with ZTS, request-scoped variables are not visible to getenv()');
+}
+--ENV--
+OTEL_TRACES_EXPORTER=console
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.cli.enabled=1
+otel.env.set_from_server=On
+--FILE--
+<?php
+$handler = new \OpenTelemetry\Auto\FrankenPhp\RequestHandler(function () {
+    var_dump(getenv('OTEL_SERVICE_NAME'));
+});
+$_SERVER['OTEL_SERVICE_NAME'] = 'first';
+$handler();
+$_SERVER['OTEL_SERVICE_NAME'] = 'second';
+$handler();
+unset($_SERVER['OTEL_SERVICE_NAME']);
+var_dump(getenv('OTEL_SERVICE_NAME'));
+?>
+--EXPECTF--
+string(5) "first"
+Spans
+Resource%A
+	 ->  service.name=String(Owned("first"))%A
+Span #0
+	Instrumentation Scope
+		Name         : "php:rinit"
+%A
+string(6) "second"
+Spans
+Resource%A
+	 ->  service.name=String(Owned("second"))%A
+Span #0
+	Instrumentation Scope
+		Name         : "php:rinit"
+%A
+bool(false)
\ No newline at end of file
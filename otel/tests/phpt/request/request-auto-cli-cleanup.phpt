@@ -19,7 +19,7 @@ otel.cli.enabled=1
 %A
 [%s] [DEBUG] [pid=%d] [ThreadId(%d)] %s message=RINIT::sapi module name is: cli
 [%s] [DEBUG] [pid=%d] [ThreadId(%d)] %s message=RINIT::tracing cli enabled by ini
-[%s] [DEBUG] [pid=%d] [ThreadId(%d)] %s message=RINIT::otel request is being traced, name=php:cli
+[%s] [DEBUG] [pid=%d] [ThreadId(%d)] %s message=RINIT::otel request is being traced, name=request-auto-cli-cleanup.php
 %A
 [%s] [DEBUG] [pid=%d] [ThreadId(%d)] %s message=RSHUTDOWN::auto-closing root span...
 %A
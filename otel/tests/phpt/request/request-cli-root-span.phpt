@@ -0,0 +1,38 @@
+--TEST--
+CLI root span with exit code and parent from TRACEPARENT
+--EXTENSIONS--
+otel
+--ENV--
+OTEL_TRACES_EXPORTER=console
+OTEL_SPAN_PROCESSOR=simple
+TRACEPARENT=00-e77388f01a826e2de7afdcd1eefc034e-d6ba64af4fa59b65-01
+--INI--
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+otel.cli.create_root_span="On"
+otel.cli.enabled=1
+--FILE--
+<?php
+exit(3);
+?>
+--EXPECTF--
+Spans
+Resource
+%A
+Span #0
+	Instrumentation Scope
+		Name         : "php:rinit"
+
+	Name         : request-cli-root-span.php
+	TraceId      : e77388f01a826e2de7afdcd1eefc034e
+	SpanId       : %s
+	TraceFlags   : TraceFlags(1)
+	ParentSpanId : d6ba64af4fa59b65
+	Kind         : Server
+	Start time   : %s
+	End time     : %s
+	Status       : Error { description: "exit code 3" }
+	Attributes:
+		 ->  process.executable.path: String(Owned("%s"))
+		 ->  process.command_args: Array(String([Owned("%s"), Owned("%srequest-cli-root-span.php")]))
+		 ->  process.exit.code: I64(3)
\ No newline at end of file
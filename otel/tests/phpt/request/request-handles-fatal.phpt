@@ -26,7 +26,7 @@ Span #0
 	Instrumentation Scope
 		Name         : "php:rinit"
 
-	Name         : php:cli
+	Name         : request-handles-fatal.php
 	TraceId      : %s
 	SpanId       : %s
 	TraceFlags   : TraceFlags(1)
@@ -34,7 +34,8 @@ Span #0
 	Kind         : Server
 	Start time   : %s
 	End time     : %s
-	Status       : Unset
+	Status       : Error { description: "exit code 255" }
 	Attributes:
-		 ->  url.full: String(Owned(""))
-		 ->  http.request.method: String(Owned(""))
\ No newline at end of file
+		 ->  process.executable.path: String(Owned("%s"))
+		 ->  process.command_args: Array(String([Owned("%s"), Owned("%srequest-handles-fatal.php")]))
+		 ->  process.exit.code: I64(255)
\ No newline at end of file
@@ -0,0 +1,21 @@
+--TEST--
+Exporter headers set from $_SERVER are redacted in the log
+--EXTENSIONS--
+otel
+--ENV--
+OTEL_EXPORTER_OTLP_HEADERS=api-key=secret
+--INI--
+otel.log.level=debug
+otel.log.file="/dev/stdout"
+otel.env.set_from_server=On
+otel.cli.enabled=On
+--FILE--
+<?php
+var_dump(getenv('OTEL_EXPORTER_OTLP_HEADERS'));
+?>
+--EXPECTF--
+%A
+[%s] [DEBUG] [pid=%d] [ThreadId(%d)] otel::request: event src/request.rs:%d message=Set environment variable from $_SERVER: OTEL_EXPORTER_OTLP_HEADERS=<redacted>
+%A
+string(14) "api-key=secret"
+%A
\ No newline at end of file
@@ -0,0 +1,51 @@
+--TEST--
+Start and end a local root span per job in a long-running worker
+--EXTENSIONS--
+otel
+--ENV--
+OTEL_TRACES_EXPORTER=memory
+OTEL_SPAN_PROCESSOR=simple
+--INI--
+otel.cli.enabled=1
+otel.log.level="error"
+otel.log.file="/dev/stdout"
+--FILE--
+<?php
+use OpenTelemetry\API\Globals;
+use OpenTelemetry\API\Trace\LocalRootSpan;
+use OpenTelemetry\API\Trace\SpanExporter\Memory;
+
+$tracer = Globals::tracerProvider()->getTracer('worker');
+$jobs = [
+    ['name' => 'job-1', 'carrier' => ['traceparent' => '00-e77388f01a826e2de7afdcd1eefc034e-d6ba64af4fa59b65-01']],
+    ['name' => 'job-2', 'carrier' => []],
+];
+foreach ($jobs as $job) {
+    $root = LocalRootSpan::start($job['name'], $job['carrier'], 4);
+    assert(LocalRootSpan::current()->getContext()->getSpanId() === $root->getContext()->getSpanId());
+    $tracer->spanBuilder('work')->startSpan()->end();
+    LocalRootSpan::end();
+    assert(LocalRootSpan::current()->getContext()->isValid() === false);
+}
+
+$spans = Memory::getSpans();
+var_dump(count($spans));
+var_dump($spans[1]['name']);
+var_dump($spans[1]['span_kind']);
+var_dump($spans[1]['parent_span_id']);
+var_dump($spans[1]['span_context']['trace_id']);
+var_dump($spans[0]['parent_span_id'] === $spans[1]['span_context']['span_id']);
+var_dump($spans[3]['name']);
+var_dump($spans[3]['parent_span_id']);
+var_dump($spans[3]['span_context']['trace_id'] !== $spans[1]['span_context']['trace_id']);
+?>
+--EXPECT--
+int(4)
+string(5) "job-1"
+string(8) "Consumer"
+string(16) "d6ba64af4fa59b65"
+string(32) "e77388f01a826e2de7afdcd1eefc034e"
+bool(true)
+string(5) "job-2"
+string(16) "0000000000000000"
+bool(true)